@@ -1,42 +1,276 @@
-use std::{fs, path::Path, sync::LazyLock};
+use crate::random;
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs,
+    io::{self, Read},
+    path::Path,
+    sync::LazyLock,
+};
 
+use owo_colors::DynColors;
 use serde::Deserialize;
 
+use crate::color::{color_from_name, color_from_rgb_entry};
+
+/// How dramatic a negative affirmation is, matched against how bad the exit
+/// code was
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    /// Bucket an exit code: 1-2 are everyday failures, 126+ (not executable,
+    /// not found, killed by a signal) are the worst
+    pub fn for_exit_code(exit_code: i32) -> Self {
+        match exit_code {
+            1..=2 => Self::Low,
+            3..=125 => Self::Medium,
+            _ => Self::High,
+        }
+    }
+}
+
+/// Which pool an affirmation is drawn from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Branch {
+    Positive,
+    Negative,
+    // Failures listed in NEUTRAL_CODES, drawn from the neutral lines
+    Neutral,
+}
+
+impl Branch {
+    pub fn for_exit_code(exit_code: i32) -> Self {
+        if exit_code == 0 {
+            Self::Positive
+        } else {
+            Self::Negative
+        }
+    }
+}
+
+impl std::str::FromStr for Branch {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "positive" => Ok(Self::Positive),
+            "negative" => Ok(Self::Negative),
+            "neutral" => Ok(Self::Neutral),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A single affirmation template. In JSON this is either a plain string or an
+/// object carrying the text plus optional selection metadata
+#[derive(Debug, Deserialize, Clone, PartialEq, Default)]
+#[serde(from = "AffirmationEntry")]
+pub struct Affirmation {
+    pub text: String,
+    pub severity: Option<Severity>,
+    pub shell: Option<String>,
+    pub persona: Option<String>,
+    // How often the line comes up relative to others, 0 counts as 1
+    pub weight: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AffirmationEntry {
+    Plain(String),
+    Tagged {
+        text: String,
+        #[serde(default)]
+        severity: Option<Severity>,
+        #[serde(default)]
+        shell: Option<String>,
+        #[serde(default)]
+        persona: Option<String>,
+        #[serde(default)]
+        weight: u32,
+    },
+}
+
+impl From<AffirmationEntry> for Affirmation {
+    fn from(entry: AffirmationEntry) -> Self {
+        match entry {
+            AffirmationEntry::Plain(text) => Self {
+                text,
+                ..Self::default()
+            },
+            AffirmationEntry::Tagged {
+                text,
+                severity,
+                shell,
+                persona,
+                weight,
+            } => Self {
+                text,
+                severity,
+                shell,
+                persona,
+                weight,
+            },
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 struct MoodSet {
-    positive: Vec<String>,
-    negative: Vec<String>,
+    positive: Vec<Affirmation>,
+    negative: Vec<Affirmation>,
+    // Optional softer lines for exit codes listed in NEUTRAL_CODES, like an
+    // interrupt. Negative lines are used when this is empty
+    #[serde(default)]
+    neutral: Vec<Affirmation>,
+    // Optional pools stitched together into one positive line in combo mode
+    #[serde(default)]
+    prefix: Vec<String>,
+    #[serde(default)]
+    suffix: Vec<String>,
+    // Optional lines for one exact exit code, keyed by the code ("130"),
+    // preferred over the positive/negative pools
+    #[serde(default)]
+    by_code: HashMap<String, Vec<Affirmation>>,
+    // Optional color for this mood's lines, used unless COLOR/COLOR_RGB is set
+    color: Option<String>,
+    color_rgb: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Default, Clone)]
 struct AffirmationsFile {
-    moods: std::collections::HashMap<String, MoodSet>,
+    moods: HashMap<String, MoodSet>,
+    #[serde(default)]
+    positive: Vec<Affirmation>,
+    #[serde(default)]
+    negative: Vec<Affirmation>,
     #[serde(default)]
-    positive: Vec<String>,
+    neutral: Vec<Affirmation>,
     #[serde(default)]
-    negative: Vec<String>,
+    prefix: Vec<String>,
+    #[serde(default)]
+    suffix: Vec<String>,
+    // Extra soft lines for when the user says "please" (beg feature), shared
+    // by every mood
+    #[serde(default)]
+    begging: Vec<Affirmation>,
+}
+
+impl MoodSet {
+    /// The mood's own color, `color_rgb` winning over `color`
+    fn color(&self) -> Option<DynColors> {
+        self.color_rgb
+            .as_deref()
+            .and_then(color_from_rgb_entry)
+            .or_else(|| self.color.as_deref().and_then(color_from_name))
+    }
+
+    fn extend(&mut self, other: MoodSet) {
+        self.positive.extend(other.positive);
+        self.negative.extend(other.negative);
+        self.neutral.extend(other.neutral);
+        self.prefix.extend(other.prefix);
+        self.suffix.extend(other.suffix);
+        for (code, lines) in other.by_code {
+            self.by_code.entry(code).or_default().extend(lines);
+        }
+        // The first file to give the mood a color keeps it
+        self.color = self.color.take().or(other.color);
+        self.color_rgb = self.color_rgb.take().or(other.color_rgb);
+    }
+
+    fn dedup(&mut self) {
+        dedup_affirmations(&mut self.positive);
+        dedup_affirmations(&mut self.negative);
+        dedup_affirmations(&mut self.neutral);
+        self.by_code.values_mut().for_each(dedup_affirmations);
+    }
+}
+
+/// Drop lines whose text already appeared earlier in the pool
+fn dedup_affirmations(pool: &mut Vec<Affirmation>) {
+    let mut seen = HashSet::new();
+    pool.retain(|a| seen.insert(a.text.clone()));
+}
+
+impl AffirmationsFile {
+    /// Add another file's lines to this one, mood by mood
+    fn merge(&mut self, other: AffirmationsFile) {
+        for (mood, set) in other.moods {
+            match self.moods.entry(mood) {
+                Entry::Occupied(mut entry) => entry.get_mut().extend(set),
+                Entry::Vacant(entry) => {
+                    entry.insert(set);
+                }
+            }
+        }
+        self.positive.extend(other.positive);
+        self.negative.extend(other.negative);
+        self.neutral.extend(other.neutral);
+        self.prefix.extend(other.prefix);
+        self.suffix.extend(other.suffix);
+        self.begging.extend(other.begging);
+    }
+
+    /// Remove exact duplicate lines from every positive and negative pool
+    fn dedup(&mut self) {
+        for set in self.moods.values_mut() {
+            set.dedup();
+        }
+        dedup_affirmations(&mut self.positive);
+        dedup_affirmations(&mut self.negative);
+        dedup_affirmations(&mut self.neutral);
+        dedup_affirmations(&mut self.begging);
+    }
+}
+
+/// `index.json` of an affirmation pack directory. Other metadata (name,
+/// author, ...) is allowed and ignored
+#[derive(Debug, Deserialize)]
+struct PackIndex {
+    files: Vec<String>,
 }
 
 #[derive(Debug)]
 pub struct AffirmationsOwned {
-    pub positive: Vec<String>,
-    pub negative: Vec<String>,
+    // Mood the lines came from, `None` for the top-level defaults
+    pub mood: Option<String>,
+    pub positive: Vec<Affirmation>,
+    pub negative: Vec<Affirmation>,
+    pub neutral: Vec<Affirmation>,
+    pub prefix: Vec<String>,
+    pub suffix: Vec<String>,
+    pub begging: Vec<Affirmation>,
+    pub by_code: HashMap<String, Vec<Affirmation>>,
+    pub color: Option<DynColors>,
 }
 
 impl AffirmationsOwned {
-    pub fn positive(&self) -> &[String] {
+    pub fn positive(&self) -> &[Affirmation] {
         &self.positive
     }
 
-    pub fn negative(&self) -> &[String] {
+    pub fn negative(&self) -> &[Affirmation] {
         &self.negative
     }
 }
 
 #[derive(Debug)]
 pub struct Affirmations<'a> {
-    pub positive: &'a [String],
-    pub negative: &'a [String],
+    pub mood: Option<&'a str>,
+    pub positive: &'a [Affirmation],
+    pub negative: &'a [Affirmation],
+    pub neutral: &'a [Affirmation],
+    pub prefix: &'a [String],
+    pub suffix: &'a [String],
+    pub begging: &'a [Affirmation],
+    pub by_code: Option<&'a HashMap<String, Vec<Affirmation>>>,
+    pub color: Option<DynColors>,
 }
 
 #[derive(Debug)]
@@ -46,69 +280,301 @@ pub enum AffirmationData<'a> {
 }
 
 impl AffirmationData<'_> {
-    pub fn positive(&self) -> &[String] {
+    pub fn mood(&self) -> Option<&str> {
+        match self {
+            Self::Owned(o) => o.mood.as_deref(),
+            Self::Borrowed(b) => b.mood,
+        }
+    }
+
+    pub fn positive(&self) -> &[Affirmation] {
         match self {
             Self::Owned(o) => o.positive(),
             Self::Borrowed(b) => b.positive,
         }
     }
 
-    pub fn negative(&self) -> &[String] {
+    pub fn negative(&self) -> &[Affirmation] {
         match self {
             Self::Owned(o) => o.negative(),
             Self::Borrowed(b) => b.negative,
         }
     }
 
+    pub fn neutral(&self) -> &[Affirmation] {
+        match self {
+            Self::Owned(o) => &o.neutral,
+            Self::Borrowed(b) => b.neutral,
+        }
+    }
+
+    /// Lines for `branch`. Neutral falls back to the negative lines when the
+    /// mood has no neutral ones
+    pub fn pool(&self, branch: Branch) -> &[Affirmation] {
+        match branch {
+            Branch::Positive => self.positive(),
+            Branch::Negative => self.negative(),
+            Branch::Neutral if self.neutral().is_empty() => self.negative(),
+            Branch::Neutral => self.neutral(),
+        }
+    }
+
+    pub fn color(&self) -> Option<DynColors> {
+        match self {
+            Self::Owned(o) => o.color,
+            Self::Borrowed(b) => b.color,
+        }
+    }
+
+    pub fn prefix(&self) -> &[String] {
+        match self {
+            Self::Owned(o) => &o.prefix,
+            Self::Borrowed(b) => b.prefix,
+        }
+    }
+
+    pub fn suffix(&self) -> &[String] {
+        match self {
+            Self::Owned(o) => &o.suffix,
+            Self::Borrowed(b) => b.suffix,
+        }
+    }
+
+    pub fn begging(&self) -> &[Affirmation] {
+        match self {
+            Self::Owned(o) => &o.begging,
+            Self::Borrowed(b) => b.begging,
+        }
+    }
+
+    /// Lines for exactly this exit code, empty if the mood has none
+    pub fn for_code(&self, exit_code: i32) -> &[Affirmation] {
+        let by_code = match self {
+            Self::Owned(o) => Some(&o.by_code),
+            Self::Borrowed(b) => b.by_code,
+        };
+        by_code
+            .and_then(|by_code| by_code.get(&exit_code.to_string()))
+            .map_or(&[], Vec::as_slice)
+    }
+
     pub fn into_owned(self) -> AffirmationData<'static> {
         match self {
             Self::Owned(o) => AffirmationData::Owned(o),
             Self::Borrowed(b) => AffirmationData::Owned(AffirmationsOwned {
+                mood: b.mood.map(str::to_string),
                 positive: b.positive.to_vec(),
                 negative: b.negative.to_vec(),
+                neutral: b.neutral.to_vec(),
+                prefix: b.prefix.to_vec(),
+                suffix: b.suffix.to_vec(),
+                begging: b.begging.to_vec(),
+                by_code: b.by_code.cloned().unwrap_or_default(),
+                color: b.color,
             }),
         }
     }
 }
 
-fn get_mood_set<'a>(file: &'a AffirmationsFile, mood: Option<&str>) -> Option<&'a MoodSet> {
-    mood.and_then(|m| file.moods.get(m))
-        .or_else(|| file.moods.get("chill"))
+fn get_mood_set<'a>(
+    file: &'a AffirmationsFile,
+    mood: Option<&str>,
+) -> Option<(&'a String, &'a MoodSet)> {
+    mood.and_then(|m| file.moods.get_key_value(m))
+        .or_else(|| file.moods.get_key_value("chill"))
 }
 
 fn affirmations_from_file<'a>(file: &'a AffirmationsFile, mood: Option<&str>) -> Affirmations<'a> {
-    if let Some(mood_set) = get_mood_set(file, mood) {
+    if let Some((name, mood_set)) = get_mood_set(file, mood) {
         Affirmations {
+            mood: Some(name),
             positive: &mood_set.positive,
             negative: &mood_set.negative,
+            neutral: &mood_set.neutral,
+            prefix: &mood_set.prefix,
+            suffix: &mood_set.suffix,
+            begging: &file.begging,
+            by_code: Some(&mood_set.by_code),
+            color: mood_set.color(),
         }
     } else {
         Affirmations {
+            mood: None,
             positive: &file.positive,
             negative: &file.negative,
+            neutral: &file.neutral,
+            prefix: &file.prefix,
+            suffix: &file.suffix,
+            begging: &file.begging,
+            by_code: None,
+            color: None,
         }
     }
 }
 
 fn affirmations_from_file_owned(file: &AffirmationsFile, mood: Option<&str>) -> AffirmationsOwned {
-    if let Some(mood_set) = get_mood_set(file, mood) {
+    if let Some((name, mood_set)) = get_mood_set(file, mood) {
         AffirmationsOwned {
+            mood: Some(name.clone()),
             positive: mood_set.positive.clone(),
             negative: mood_set.negative.clone(),
+            neutral: mood_set.neutral.clone(),
+            prefix: mood_set.prefix.clone(),
+            suffix: mood_set.suffix.clone(),
+            begging: file.begging.clone(),
+            by_code: mood_set.by_code.clone(),
+            color: mood_set.color(),
         }
     } else {
         AffirmationsOwned {
+            mood: None,
             positive: file.positive.clone(),
             negative: file.negative.clone(),
+            neutral: file.neutral.clone(),
+            prefix: file.prefix.clone(),
+            suffix: file.suffix.clone(),
+            begging: file.begging.clone(),
+            by_code: HashMap::new(),
+            color: None,
         }
     }
 }
 
+/// Glyph used to mark which mood an affirmation came from
+pub fn mood_glyph(mood: &str) -> &'static str {
+    match mood {
+        "chill" => "🌸",
+        "ominous" => "🌑",
+        "thirsty" => "💦",
+        _ => "💬",
+    }
+}
+
+/// Stitch one random prefix and one random suffix into a single line.
+/// `None` unless both pools have something in them
+pub fn combo_template(prefix: &[String], suffix: &[String]) -> Option<String> {
+    if prefix.is_empty() || suffix.is_empty() {
+        return None;
+    }
+    let start = &prefix[random::usize(..prefix.len())];
+    let end = &suffix[random::usize(..suffix.len())];
+    Some(format!("{start}{end}"))
+}
+
+/// Keep only the candidates matching `keep`, unless that would leave nothing
+fn prefer(candidates: &mut Vec<&Affirmation>, keep: impl Fn(&Affirmation) -> bool) {
+    if candidates.iter().any(|a| keep(a)) {
+        candidates.retain(|a| keep(a));
+    }
+}
+
+/// Narrow candidates down to lines tagged with the given severity, keeping
+/// untagged lines eligible. Leaves the candidates alone if nothing would be left
+pub fn prefer_severity(candidates: &mut Vec<&Affirmation>, severity: Severity) {
+    prefer(candidates, |a| a.severity.is_none_or(|s| s == severity));
+}
+
+/// Drop lines tagged for a different shell than the one we're running under,
+/// keeping untagged lines eligible
+pub fn prefer_shell(candidates: &mut Vec<&Affirmation>, shell: Option<&str>) {
+    prefer(candidates, |a| {
+        a.shell.as_deref().is_none_or(|s| Some(s) == shell)
+    });
+}
+
+/// Narrow candidates down to lines tagged with the given persona. Untagged
+/// lines stay eligible unless `strict` is set
+pub fn prefer_persona(candidates: &mut Vec<&Affirmation>, persona: &str, strict: bool) {
+    prefer(candidates, |a| match a.persona.as_deref() {
+        Some(p) => p.eq_ignore_ascii_case(persona),
+        None => !strict,
+    });
+}
+
+/// Narrow candidates down to lines that haven't been shown yet
+pub fn prefer_unseen(candidates: &mut Vec<&Affirmation>, seen: &[String]) {
+    prefer(candidates, |a| !seen.contains(&a.text));
+}
+
+pub const EMBEDDED_AFFIRMATIONS_JSON: &str = include_str!("../assets/affirmations.json");
+
 static EMBEDDED_AFFIRMATIONS: LazyLock<AffirmationsFile> = LazyLock::new(|| {
-    serde_json::from_str(include_str!("../assets/affirmations.json"))
-        .expect("Failed to parse embedded affirmations")
+    serde_json::from_str(EMBEDDED_AFFIRMATIONS_JSON).expect("Failed to parse embedded affirmations")
 });
 
+/// Check that a string parses as an affirmations file
+pub fn check_affirmations(json_str: &str) -> serde_json::Result<()> {
+    serde_json::from_str::<AffirmationsFile>(json_str).map(|_| ())
+}
+
+/// Parse an affirmations file, as TOML if `path` ends in `.toml` and as JSON
+/// otherwise
+fn parse_affirmations_file(path: &Path, contents: &str) -> Result<AffirmationsFile, String> {
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+    {
+        toml::from_str(contents).map_err(|e| e.to_string())
+    } else {
+        serde_json::from_str(contents).map_err(|e| e.to_string())
+    }
+}
+
+/// Line counts of one mood in an affirmations file
+#[derive(Debug, PartialEq)]
+pub struct MoodSummary {
+    pub name: String,
+    pub positive: usize,
+    pub negative: usize,
+}
+
+/// The moods in the affirmations file at `path` with their line counts,
+/// sorted by name
+pub fn mood_summaries(path: &Path, contents: &str) -> Result<Vec<MoodSummary>, String> {
+    let file = parse_affirmations_file(path, contents)?;
+    let mut moods: Vec<MoodSummary> = file
+        .moods
+        .iter()
+        .map(|(name, set)| MoodSummary {
+            name: name.clone(),
+            positive: set.positive.len(),
+            negative: set.negative.len(),
+        })
+        .collect();
+    moods.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(moods)
+}
+
+/// Every template in the affirmations file at `path`, across all moods and
+/// pools
+pub fn affirmation_texts(path: &Path, contents: &str) -> Result<Vec<String>, String> {
+    let file = parse_affirmations_file(path, contents)?;
+    let pools = |positive: &[Affirmation],
+                 negative: &[Affirmation],
+                 prefix: &[String],
+                 suffix: &[String]| {
+        positive
+            .iter()
+            .chain(negative)
+            .map(|a| a.text.clone())
+            .chain(prefix.iter().chain(suffix).cloned())
+            .collect::<Vec<_>>()
+    };
+
+    let mut texts = pools(&file.positive, &file.negative, &file.prefix, &file.suffix);
+    texts.extend(file.begging.iter().map(|a| a.text.clone()));
+    for set in file.moods.values() {
+        texts.extend(pools(
+            &set.positive,
+            &set.negative,
+            &set.prefix,
+            &set.suffix,
+        ));
+        texts.extend(set.by_code.values().flatten().map(|a| a.text.clone()));
+    }
+    Ok(texts)
+}
+
 pub fn load_affirmations_with_mood(mood: &str) -> Option<AffirmationData<'static>> {
     Some(AffirmationData::Borrowed(affirmations_from_file(
         &EMBEDDED_AFFIRMATIONS,
@@ -123,25 +589,25 @@ fn mix_moods<'a>(
     probability: f32,
 ) -> Option<AffirmationData<'a>> {
     // Check probability first to avoid cloning if not mixing
-    if fastrand::f32() >= probability {
+    if random::f32() >= probability {
         return None;
     }
 
-    let primary_set = file.moods.get(primary_mood)?;
+    let (primary_name, primary_set) = file.moods.get_key_value(primary_mood)?;
     let secondary_set = file.moods.get(secondary_mood)?;
 
     let mut mixed_positive = primary_set.positive.clone();
     let mut mixed_negative = primary_set.negative.clone();
     let mut changed = false;
 
-    let mix = |source: &[String], target: &mut [String]| {
+    let mix = |source: &[Affirmation], target: &mut [Affirmation]| {
         if !source.is_empty() && !target.is_empty() {
-            let idx = fastrand::usize(..source.len());
+            let idx = random::usize(..source.len());
             if let Some(secondary_affirmation) = source.get(idx) {
-                let primary_idx = fastrand::usize(..target.len());
+                let primary_idx = random::usize(..target.len());
                 let _ = std::fmt::Write::write_fmt(
-                    &mut target[primary_idx],
-                    format_args!(" {secondary_affirmation}"),
+                    &mut target[primary_idx].text,
+                    format_args!(" {}", secondary_affirmation.text),
                 );
                 return true;
             }
@@ -154,13 +620,27 @@ fn mix_moods<'a>(
 
     if changed {
         Some(AffirmationData::Owned(AffirmationsOwned {
+            mood: Some(primary_name.clone()),
             positive: mixed_positive,
             negative: mixed_negative,
+            neutral: primary_set.neutral.clone(),
+            prefix: primary_set.prefix.clone(),
+            suffix: primary_set.suffix.clone(),
+            begging: file.begging.clone(),
+            by_code: primary_set.by_code.clone(),
+            color: primary_set.color(),
         }))
     } else {
         Some(AffirmationData::Borrowed(Affirmations {
+            mood: Some(primary_name),
             positive: &primary_set.positive,
             negative: &primary_set.negative,
+            neutral: &primary_set.neutral,
+            prefix: &primary_set.prefix,
+            suffix: &primary_set.suffix,
+            begging: &file.begging,
+            by_code: Some(&primary_set.by_code),
+            color: primary_set.color(),
         }))
     }
 }
@@ -183,33 +663,212 @@ pub fn load_affirmations_with_mood_mixing(
     load_affirmations_with_mood(mood)
 }
 
-/// Load custom affirmations with optional mood mixing support
+/// Default cap on the size of a custom affirmations file
+pub const DEFAULT_MAX_AFFIRMATIONS_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Read a file, giving up without slurping the rest once it grows past
+/// `max_bytes`. Returns `Ok(None)` if the file is too large
+fn read_capped<P: AsRef<Path>>(path: P, max_bytes: u64) -> io::Result<Option<String>> {
+    let mut contents = String::new();
+    fs::File::open(path)?
+        .take(max_bytes.saturating_add(1))
+        .read_to_string(&mut contents)?;
+    Ok((contents.len() as u64 <= max_bytes).then_some(contents))
+}
+
+/// Pick the lines for `mood` out of an already parsed file, with optional
+/// mood mixing
+fn owned_affirmations_with_mood_mixing(
+    file: &AffirmationsFile,
+    mood: &str,
+    enable_mixing: bool,
+) -> AffirmationData<'static> {
+    if enable_mixing && mood == "ominous" {
+        // Mix ominous with thirsty (20% chance)
+        if let Some(mixed) = mix_moods(file, "ominous", "thirsty", 0.2) {
+            return mixed.into_owned();
+        }
+    }
+
+    AffirmationData::Owned(affirmations_from_file_owned(file, Some(mood)))
+}
+
+/// Load custom affirmations with optional mood mixing support. Files larger
+/// than `max_bytes` are skipped in favor of the embedded affirmations, with a
+/// message added to `warnings`
 pub fn load_custom_affirmations_with_mood_mixing<P: AsRef<Path>>(
     path: P,
     mood: &str,
     enable_mixing: bool,
+    max_bytes: u64,
+    warnings: &mut Vec<String>,
 ) -> Option<AffirmationData<'static>> {
-    let json_str = fs::read_to_string(&path).ok()?;
-    let file: AffirmationsFile = serde_json::from_str(&json_str).ok()?;
+    let Some(json_str) = read_capped(&path, max_bytes).ok()? else {
+        warnings.push(format!(
+            "mommy: {} is larger than {max_bytes} bytes, using the built-in affirmations",
+            path.as_ref().display()
+        ));
+        return load_affirmations_with_mood_mixing(mood, enable_mixing);
+    };
+    let file = parse_affirmations_file(path.as_ref(), &json_str).ok()?;
 
-    if enable_mixing && mood == "ominous" {
-        // Mix ominous with thirsty (20% chance)
-        if let Some(mixed) = mix_moods(&file, "ominous", "thirsty", 0.2) {
-            return Some(mixed.into_owned());
+    Some(owned_affirmations_with_mood_mixing(
+        &file,
+        mood,
+        enable_mixing,
+    ))
+}
+
+/// Separator between the paths in an `AFFIRMATIONS` list, like in `PATH`
+const PATH_LIST_SEPARATOR: char = if cfg!(windows) { ';' } else { ':' };
+
+/// The paths in an `AFFIRMATIONS` value, which may list several files
+/// separated like `PATH`
+pub fn split_affirmation_paths(value: &str) -> Vec<&str> {
+    value
+        .split(PATH_LIST_SEPARATOR)
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .collect()
+}
+
+/// Read and merge several affirmations files in order. Files that are
+/// missing, too large or malformed are skipped with a message added to
+/// `warnings`. `None` if none of them loads
+fn read_affirmation_files<P: AsRef<Path>>(
+    paths: &[P],
+    max_bytes: u64,
+    warnings: &mut Vec<String>,
+) -> Option<AffirmationsFile> {
+    let mut merged: Option<AffirmationsFile> = None;
+    for path in paths {
+        let path = path.as_ref();
+        let file = match read_capped(path, max_bytes) {
+            Ok(Some(contents)) => parse_affirmations_file(path, &contents).ok(),
+            _ => None,
+        };
+        match file {
+            Some(file) => merged.get_or_insert_default().merge(file),
+            None => warnings.push(format!(
+                "mommy: skipping {}, it's missing, too large or not valid affirmations",
+                path.display()
+            )),
         }
+    }
+    merged
+}
 
-        // Fallback: use the already parsed file
-        return Some(AffirmationData::Owned(affirmations_from_file_owned(
-            &file,
-            Some(mood),
-        )));
+/// Load several custom affirmation files merged together: lines are
+/// concatenated in file order, and a mood in more than one file gets the
+/// lines of all of them. Files that can't be loaded are skipped with a warning
+pub fn load_custom_affirmations_multi(
+    paths: &[&str],
+    mood: &str,
+    enable_mixing: bool,
+    max_bytes: u64,
+    warnings: &mut Vec<String>,
+) -> Option<AffirmationData<'static>> {
+    let file = read_affirmation_files(paths, max_bytes, warnings)?;
+    Some(owned_affirmations_with_mood_mixing(
+        &file,
+        mood,
+        enable_mixing,
+    ))
+}
+
+/// Load custom affirmations appended to the embedded ones, with exact
+/// duplicates removed (the embedded line wins). Moods the custom files add are
+/// available alongside the built-in ones. If no custom file can be loaded,
+/// only the embedded affirmations are used
+pub fn load_merged_affirmations_with_mood_mixing<P: AsRef<Path>>(
+    paths: &[P],
+    mood: &str,
+    enable_mixing: bool,
+    max_bytes: u64,
+    warnings: &mut Vec<String>,
+) -> Option<AffirmationData<'static>> {
+    let Some(custom) = read_affirmation_files(paths, max_bytes, warnings) else {
+        warnings.push("mommy: using only the built-in affirmations".to_string());
+        return load_affirmations_with_mood_mixing(mood, enable_mixing);
+    };
+
+    let mut file = EMBEDDED_AFFIRMATIONS.clone();
+    file.merge(custom);
+    file.dedup();
+    Some(owned_affirmations_with_mood_mixing(
+        &file,
+        mood,
+        enable_mixing,
+    ))
+}
+
+/// Read an affirmation pack directory: every member file listed in its
+/// `index.json`, merged together. Members that are missing, too large or
+/// malformed are skipped with a message added to `warnings`
+fn read_pack(dir: &Path, max_bytes: u64, warnings: &mut Vec<String>) -> Option<AffirmationsFile> {
+    let index: PackIndex =
+        serde_json::from_str(&read_capped(dir.join("index.json"), max_bytes).ok()??).ok()?;
+
+    let mut merged = AffirmationsFile::default();
+    for member in &index.files {
+        let path = dir.join(member);
+        let file = match read_capped(&path, max_bytes) {
+            Ok(Some(contents)) => parse_affirmations_file(&path, &contents).ok(),
+            _ => None,
+        };
+        match file {
+            Some(file) => merged.merge(file),
+            None => warnings.push(format!(
+                "mommy: skipping pack member {}, it's missing, too large or not valid affirmations",
+                path.display()
+            )),
+        }
     }
+    Some(merged)
+}
 
-    // Fall back to regular mood loading
-    Some(AffirmationData::Owned(affirmations_from_file_owned(
+/// Moods a file offers, sorted. `chill` is always included: it's the default
+/// mood, and a file without it uses its top-level lines instead
+fn list_moods(file: &AffirmationsFile) -> Vec<String> {
+    let mut moods: Vec<String> = file.moods.keys().cloned().collect();
+    moods.push("chill".to_string());
+    moods.sort();
+    moods.dedup();
+    moods
+}
+
+/// Moods available from the pack directory if given, else the custom files if
+/// given, else the embedded affirmations. `None` if they can't be loaded
+pub fn available_moods(
+    pack: Option<&str>,
+    custom: Option<&str>,
+    max_bytes: u64,
+    warnings: &mut Vec<String>,
+) -> Option<Vec<String>> {
+    if let Some(dir) = pack {
+        return read_pack(Path::new(dir), max_bytes, warnings).map(|file| list_moods(&file));
+    }
+    if let Some(paths) = custom {
+        return read_affirmation_files(&split_affirmation_paths(paths), max_bytes, warnings)
+            .map(|file| list_moods(&file));
+    }
+    Some(list_moods(&EMBEDDED_AFFIRMATIONS))
+}
+
+/// Load affirmations from a pack directory with optional mood mixing support
+pub fn load_pack_affirmations_with_mood_mixing<P: AsRef<Path>>(
+    dir: P,
+    mood: &str,
+    enable_mixing: bool,
+    max_bytes: u64,
+    warnings: &mut Vec<String>,
+) -> Option<AffirmationData<'static>> {
+    let file = read_pack(dir.as_ref(), max_bytes, warnings)?;
+    Some(owned_affirmations_with_mood_mixing(
         &file,
-        Some(mood),
-    )))
+        mood,
+        enable_mixing,
+    ))
 }
 
 #[cfg(test)]
@@ -246,7 +905,27 @@ mod tests {
         assert!(affirmations
             .positive()
             .iter()
-            .any(|s| s == "*boops your nose* {emotes}"));
+            .any(|s| s.text == "*boops your nose* {emotes}"));
+    }
+
+    #[test]
+    fn test_embedded_affirmations_cached() {
+        let fresh: AffirmationsFile = serde_json::from_str(EMBEDDED_AFFIRMATIONS_JSON).unwrap();
+        let texts = |pool: &[Affirmation]| pool.iter().map(|a| a.text.clone()).collect::<Vec<_>>();
+
+        for mood in list_moods(&fresh) {
+            let first = load_affirmations_with_mood(&mood).unwrap();
+            let second = load_affirmations_with_mood(&mood).unwrap();
+
+            // Expect: the cached file holds the same lines as a fresh parse
+            let set = &fresh.moods[&mood];
+            assert_eq!(texts(first.positive()), texts(&set.positive));
+            assert_eq!(texts(first.negative()), texts(&set.negative));
+
+            // Expect: every load borrows the one cached instance
+            assert!(std::ptr::eq(first.positive(), second.positive()));
+            assert!(std::ptr::eq(first.negative(), second.negative()));
+        }
     }
 
     #[test]
@@ -257,17 +936,23 @@ mod tests {
         assert!(aff
             .positive()
             .iter()
-            .any(|s| s == "you're such a smart cookie~ {emotes}"));
+            .any(|s| s.text == "you're such a smart cookie~ {emotes}"));
         assert!(aff
             .negative()
             .iter()
-            .any(|s| s == "{roles} believes in you~ {emotes}"));
+            .any(|s| s.text == "{roles} believes in you~ {emotes}"));
     }
 
     #[test]
     fn load_custom_affirmations_missing_file() {
         let path = "/nonexistent/path/to/file";
-        let aff = load_custom_affirmations_with_mood_mixing(path, "chill", false);
+        let aff = load_custom_affirmations_with_mood_mixing(
+            path,
+            "chill",
+            false,
+            DEFAULT_MAX_AFFIRMATIONS_BYTES,
+            &mut Vec::new(),
+        );
 
         // Expect: None for nonexistent path
         assert!(aff.is_none(), "expected None for bad path, got {aff:#?}");
@@ -293,7 +978,7 @@ mod tests {
             affirmations
                 .positive()
                 .iter()
-                .any(|s| s.contains("aeons") || s.contains("feared")),
+                .any(|s| s.text.contains("aeons") || s.text.contains("feared")),
             "expected ominous-themed positive affirmations"
         );
     }
@@ -450,4 +1135,467 @@ mod tests {
             "should have one negative affirmation"
         );
     }
+
+    #[test]
+    fn test_mood_glyph() {
+        assert_eq!(mood_glyph("ominous"), "🌑");
+        assert_eq!(mood_glyph("chill"), "🌸");
+        assert_eq!(mood_glyph("thirsty"), "💦");
+
+        // Expect: unknown moods share the default glyph
+        assert_eq!(mood_glyph("nonexistent"), "💬");
+        assert_eq!(mood_glyph(""), "💬");
+    }
+
+    #[test]
+    fn test_affirmation_objects_parse() {
+        let test_json = r#"{
+            "moods": {
+                "chill": {
+                    "positive": ["plain line", { "text": "object line" }, { "text": "often", "weight": 3 }],
+                    "negative": [{ "text": "oh no", "severity": "high" }]
+                }
+            }
+        }"#;
+
+        let file: AffirmationsFile =
+            serde_json::from_str(test_json).expect("test JSON should parse");
+        let affirmations = affirmations_from_file(&file, Some("chill"));
+
+        // Expect: plain strings and objects both become affirmations
+        assert_eq!(affirmations.positive[0].text, "plain line");
+        assert_eq!(affirmations.positive[0].severity, None);
+        assert_eq!(affirmations.positive[1].text, "object line");
+        assert_eq!(affirmations.negative[0].severity, Some(Severity::High));
+
+        // Expect: weights parse, missing ones are left at 0 (counts as 1)
+        assert_eq!(affirmations.positive[0].weight, 0);
+        assert_eq!(affirmations.positive[2].weight, 3);
+    }
+
+    #[test]
+    fn test_severity_for_exit_code() {
+        assert_eq!(Severity::for_exit_code(1), Severity::Low);
+        assert_eq!(Severity::for_exit_code(2), Severity::Low);
+        assert_eq!(Severity::for_exit_code(101), Severity::Medium);
+        assert_eq!(Severity::for_exit_code(127), Severity::High);
+        assert_eq!(Severity::for_exit_code(137), Severity::High);
+    }
+
+    #[test]
+    fn test_prefer_severity() {
+        let line = |text: &str, severity| Affirmation {
+            text: text.to_string(),
+            severity,
+            ..Affirmation::default()
+        };
+        let pool = [
+            line("low", Some(Severity::Low)),
+            line("high", Some(Severity::High)),
+            line("untagged", None),
+        ];
+
+        // Expect: a high exit code keeps high-severity and untagged lines
+        let mut candidates: Vec<&Affirmation> = pool.iter().collect();
+        prefer_severity(&mut candidates, Severity::for_exit_code(130));
+        let texts: Vec<&str> = candidates.iter().map(|a| a.text.as_str()).collect();
+        assert_eq!(texts, vec!["high", "untagged"]);
+
+        // Expect: only the high-severity line is left without untagged lines
+        let mut candidates: Vec<&Affirmation> = pool[..2].iter().collect();
+        prefer_severity(&mut candidates, Severity::High);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].text, "high");
+
+        // Expect: nothing matching leaves the pool as it was
+        let mut candidates: Vec<&Affirmation> = pool[..1].iter().collect();
+        prefer_severity(&mut candidates, Severity::Medium);
+        assert_eq!(candidates.len(), 1);
+    }
+
+    #[test]
+    fn test_custom_affirmations_size_cap() {
+        let test_json = r#"{
+            "moods": {
+                "chill": {
+                    "positive": ["custom positive"],
+                    "negative": ["custom negative"]
+                }
+            }
+        }"#;
+        let path = std::env::temp_dir().join(format!("mommy-test-cap-{}.json", std::process::id()));
+        fs::write(&path, test_json).unwrap();
+
+        // Expect: under the cap the custom file loads normally
+        let mut warnings = Vec::new();
+        let under =
+            load_custom_affirmations_with_mood_mixing(&path, "chill", false, 4096, &mut warnings)
+                .expect("file under the cap should load");
+        assert_eq!(under.positive()[0].text, "custom positive");
+        assert!(warnings.is_empty());
+
+        // Expect: over the cap it's rejected in favor of the embedded lines,
+        // and the caller is told why instead of anything being printed
+        let over =
+            load_custom_affirmations_with_mood_mixing(&path, "chill", false, 16, &mut warnings)
+                .expect("oversized file should fall back to embedded");
+        assert!(over
+            .positive()
+            .iter()
+            .any(|s| s.text == "*boops your nose* {emotes}"));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("larger than 16 bytes"));
+
+        assert!(read_capped(&path, 16).unwrap().is_none());
+        assert!(read_capped(&path, test_json.len() as u64)
+            .unwrap()
+            .is_some());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_custom_affirmations_toml() {
+        let test_toml = r#"
+            positive = ["top-level positive"]
+            negative = ["top-level negative"]
+
+            [moods.thirsty]
+            positive = ["toml thirsty", { text = "weighted", weight = 2 }]
+            negative = [{ text = "toml oh no", severity = "high" }]
+        "#;
+        let path = std::env::temp_dir().join(format!("mommy-test-{}.toml", std::process::id()));
+        fs::write(&path, test_toml).unwrap();
+
+        // Expect: the mood lookup works like in JSON files
+        let thirsty = load_custom_affirmations_with_mood_mixing(
+            &path,
+            "thirsty",
+            false,
+            4096,
+            &mut Vec::new(),
+        )
+        .expect("TOML file should load");
+        assert_eq!(thirsty.positive()[0].text, "toml thirsty");
+        assert_eq!(thirsty.positive()[1].weight, 2);
+        assert_eq!(thirsty.negative()[0].severity, Some(Severity::High));
+
+        // Expect: an unknown mood without a chill mood uses the top-level lines
+        let fallback = load_custom_affirmations_with_mood_mixing(
+            &path,
+            "ominous",
+            false,
+            4096,
+            &mut Vec::new(),
+        )
+        .expect("TOML file should load");
+        assert_eq!(fallback.positive()[0].text, "top-level positive");
+
+        // Expect: broken TOML fails just like broken JSON
+        fs::write(&path, "moods = [not toml").unwrap();
+        assert!(load_custom_affirmations_with_mood_mixing(
+            &path,
+            "chill",
+            false,
+            4096,
+            &mut Vec::new()
+        )
+        .is_none());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_merged_affirmations() {
+        let embedded = load_affirmations_with_mood_mixing("chill", false).unwrap();
+        let builtin = embedded.positive()[0].text.clone();
+        let test_json = format!(
+            r#"{{"moods": {{"chill": {{"positive": [{builtin:?}, "merged positive"], "negative": ["merged negative"]}}}}}}"#
+        );
+        let path =
+            std::env::temp_dir().join(format!("mommy-test-merge-{}.json", std::process::id()));
+        fs::write(&path, test_json).unwrap();
+
+        // Expect: embedded lines first, then the custom ones, without the duplicate
+        let merged = load_merged_affirmations_with_mood_mixing(
+            &[&path],
+            "chill",
+            false,
+            4096,
+            &mut Vec::new(),
+        )
+        .expect("merged affirmations should load");
+        let positive: Vec<_> = merged.positive().iter().map(|a| a.text.as_str()).collect();
+        assert_eq!(positive.len(), embedded.positive().len() + 1);
+        assert_eq!(positive[0], builtin);
+        assert_eq!(positive.last(), Some(&"merged positive"));
+        assert_eq!(positive.iter().filter(|t| **t == builtin).count(), 1);
+        assert_eq!(
+            merged.negative().last().map(|a| a.text.as_str()),
+            Some("merged negative")
+        );
+
+        // Expect: a broken custom file falls back to the embedded lines only
+        fs::write(&path, "{ not json").unwrap();
+        let fallback = load_merged_affirmations_with_mood_mixing(
+            &[&path],
+            "chill",
+            false,
+            4096,
+            &mut Vec::new(),
+        )
+        .expect("should fall back to the embedded affirmations");
+        assert_eq!(fallback.positive().len(), embedded.positive().len());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_custom_affirmations_multi() {
+        let dir = std::env::temp_dir();
+        let first = dir.join(format!("mommy-test-multi-a-{}.json", std::process::id()));
+        let second = dir.join(format!("mommy-test-multi-b-{}.toml", std::process::id()));
+        fs::write(
+            &first,
+            r#"{"moods": {"chill": {"positive": ["first~"], "negative": ["first no~"]}}}"#,
+        )
+        .unwrap();
+        fs::write(
+            &second,
+            "[moods.chill]\npositive = [\"second~\"]\nnegative = []\n\n\
+             [moods.thirsty]\npositive = [\"only here~\"]\nnegative = []\n",
+        )
+        .unwrap();
+        let list = format!(
+            "{}{PATH_LIST_SEPARATOR}{}{PATH_LIST_SEPARATOR}/nonexistent/mommy.json",
+            first.display(),
+            second.display()
+        );
+        let paths = split_affirmation_paths(&list);
+        assert_eq!(paths.len(), 3);
+
+        // Expect: a mood in both files gets the lines of both, in order, and
+        // the missing file is skipped
+        let mut warnings = Vec::new();
+        let chill =
+            load_custom_affirmations_multi(&paths, "chill", false, 4096, &mut warnings).unwrap();
+        let texts: Vec<_> = chill.positive().iter().map(|a| a.text.as_str()).collect();
+        assert_eq!(texts, vec!["first~", "second~"]);
+        assert_eq!(chill.negative().len(), 1);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("/nonexistent/mommy.json"));
+
+        // Expect: a mood from only one file is available too
+        let thirsty =
+            load_custom_affirmations_multi(&paths, "thirsty", false, 4096, &mut Vec::new())
+                .unwrap();
+        assert_eq!(thirsty.mood(), Some("thirsty"));
+        assert_eq!(thirsty.positive()[0].text, "only here~");
+        assert_eq!(
+            available_moods(None, Some(&list), 4096, &mut Vec::new()),
+            Some(vec!["chill".to_string(), "thirsty".to_string()])
+        );
+
+        // Expect: nothing loads, nothing to use
+        assert!(load_custom_affirmations_multi(
+            &["/nonexistent/a.json"],
+            "chill",
+            false,
+            4096,
+            &mut Vec::new()
+        )
+        .is_none());
+
+        fs::remove_file(&first).unwrap();
+        fs::remove_file(&second).unwrap();
+    }
+
+    #[test]
+    fn test_list_moods() {
+        let moods = list_moods(&EMBEDDED_AFFIRMATIONS);
+        assert!(moods.contains(&"ominous".to_string()));
+        assert!(moods.contains(&"thirsty".to_string()));
+        assert!(moods.is_sorted());
+
+        // Expect: a file without moods still offers the default
+        let file: AffirmationsFile =
+            serde_json::from_str(r#"{"moods": {}, "positive": ["a"], "negative": ["b"]}"#).unwrap();
+        assert_eq!(list_moods(&file), vec!["chill"]);
+    }
+
+    #[test]
+    fn test_load_pack() {
+        let dir = std::env::temp_dir().join(format!("mommy-test-pack-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("index.json"),
+            r#"{"name": "test pack", "files": ["one.json", "two.json", "missing.json"]}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("one.json"),
+            r#"{"moods": {"chill": {"positive": ["one positive"], "negative": ["one negative"]}}}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("two.json"),
+            r#"{"moods": {
+                "chill": {"positive": ["two positive"], "negative": []},
+                "thirsty": {"positive": ["two thirsty"], "negative": []}
+            }}"#,
+        )
+        .unwrap();
+
+        // Expect: both members' chill lines end up in one pool, the missing
+        // member is skipped
+        let mut warnings = Vec::new();
+        let chill =
+            load_pack_affirmations_with_mood_mixing(&dir, "chill", false, 4096, &mut warnings)
+                .expect("pack should load");
+        let texts: Vec<&str> = chill.positive().iter().map(|a| a.text.as_str()).collect();
+        assert_eq!(texts, vec!["one positive", "two positive"]);
+        assert_eq!(chill.negative().len(), 1);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("missing.json"));
+
+        // Expect: moods only one member has are still available
+        let thirsty =
+            load_pack_affirmations_with_mood_mixing(&dir, "thirsty", false, 4096, &mut Vec::new())
+                .expect("pack should load");
+        assert_eq!(thirsty.positive()[0].text, "two thirsty");
+
+        // Expect: no index, no pack
+        fs::remove_file(dir.join("index.json")).unwrap();
+        assert!(load_pack_affirmations_with_mood_mixing(
+            &dir,
+            "chill",
+            false,
+            4096,
+            &mut Vec::new()
+        )
+        .is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_prefer_unseen() {
+        let line = |text: &str| Affirmation {
+            text: text.to_string(),
+            ..Affirmation::default()
+        };
+        let pool = [line("a"), line("b"), line("c")];
+
+        // Expect: seen lines are dropped
+        let mut candidates: Vec<&Affirmation> = pool.iter().collect();
+        prefer_unseen(&mut candidates, &["a".to_string(), "c".to_string()]);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].text, "b");
+
+        // Expect: everything stays eligible once the whole pool was seen
+        let seen: Vec<String> = pool.iter().map(|a| a.text.clone()).collect();
+        let mut candidates: Vec<&Affirmation> = pool.iter().collect();
+        prefer_unseen(&mut candidates, &seen);
+        assert_eq!(candidates.len(), 3);
+    }
+
+    #[test]
+    fn test_prefer_shell() {
+        let line = |text: &str, shell: Option<&str>| Affirmation {
+            text: text.to_string(),
+            shell: shell.map(str::to_string),
+            ..Affirmation::default()
+        };
+        let pool = [
+            line("fish", Some("fish")),
+            line("zsh", Some("zsh")),
+            line("untagged", None),
+        ];
+        let texts = |candidates: &[&Affirmation]| -> Vec<String> {
+            candidates.iter().map(|a| a.text.clone()).collect()
+        };
+
+        // Expect: the fish line is eligible under fish, the zsh one isn't
+        let mut candidates: Vec<&Affirmation> = pool.iter().collect();
+        prefer_shell(&mut candidates, Some("fish"));
+        assert_eq!(texts(&candidates), vec!["fish", "untagged"]);
+
+        // Expect: only the fish line among shell-tagged lines
+        let mut candidates: Vec<&Affirmation> = pool[..2].iter().collect();
+        prefer_shell(&mut candidates, Some("fish"));
+        assert_eq!(texts(&candidates), vec!["fish"]);
+
+        // Expect: fish lines are ignored under other shells
+        let mut candidates: Vec<&Affirmation> = pool.iter().collect();
+        prefer_shell(&mut candidates, Some("bash"));
+        assert_eq!(texts(&candidates), vec!["untagged"]);
+
+        let mut candidates: Vec<&Affirmation> = pool.iter().collect();
+        prefer_shell(&mut candidates, None);
+        assert_eq!(texts(&candidates), vec!["untagged"]);
+    }
+
+    #[test]
+    fn test_combo_template() {
+        let test_json = r#"{
+            "moods": {
+                "chill": {
+                    "positive": ["single line"],
+                    "negative": ["single negative"],
+                    "prefix": ["you did it!", "look at you!"],
+                    "suffix": [" {roles} is proud~", " {roles} is so happy~"]
+                }
+            }
+        }"#;
+        let file: AffirmationsFile =
+            serde_json::from_str(test_json).expect("test JSON should parse");
+        let affirmations = affirmations_from_file(&file, Some("chill"));
+
+        // Expect: one prefix followed by one suffix
+        fastrand::seed(42);
+        let combo = combo_template(affirmations.prefix, affirmations.suffix).unwrap();
+        assert!(
+            affirmations.prefix.iter().any(|p| {
+                combo
+                    .strip_prefix(p.as_str())
+                    .is_some_and(|rest| affirmations.suffix.iter().any(|s| s == rest))
+            }),
+            "unexpected combo {combo}"
+        );
+
+        // Expect: no combo when either pool is missing
+        assert_eq!(combo_template(affirmations.prefix, &[]), None);
+        assert_eq!(combo_template(&[], affirmations.suffix), None);
+    }
+
+    #[test]
+    fn test_prefer_persona() {
+        let line = |text: &str, persona: Option<&str>| Affirmation {
+            text: text.to_string(),
+            persona: persona.map(str::to_string),
+            ..Affirmation::default()
+        };
+        let pool = [
+            line("strict", Some("strict")),
+            line("goofy", Some("goofy")),
+            line("untagged", None),
+        ];
+        let texts = |candidates: &[&Affirmation]| -> Vec<String> {
+            candidates.iter().map(|a| a.text.clone()).collect()
+        };
+
+        // Expect: inclusive filtering keeps untagged lines
+        let mut candidates: Vec<&Affirmation> = pool.iter().collect();
+        prefer_persona(&mut candidates, "goofy", false);
+        assert_eq!(texts(&candidates), vec!["goofy", "untagged"]);
+
+        // Expect: strict filtering keeps only the matching persona
+        let mut candidates: Vec<&Affirmation> = pool.iter().collect();
+        prefer_persona(&mut candidates, "Strict", true);
+        assert_eq!(texts(&candidates), vec!["strict"]);
+
+        // Expect: nothing matching leaves the pool as it was
+        let mut candidates: Vec<&Affirmation> = pool.iter().collect();
+        prefer_persona(&mut candidates, "soft", true);
+        assert_eq!(candidates.len(), 3);
+    }
 }
@@ -1,5 +1,9 @@
-use crate::config::ConfigMommy;
-use owo_colors::{DynColors, Style};
+use crate::{config::ConfigMommy, random};
+use owo_colors::{DynColors, OwoColorize, Rgb, Style, XtermColors};
+use std::{
+    fmt,
+    io::{self, IsTerminal},
+};
 
 #[inline]
 pub fn color_from_name(name: &str) -> Option<DynColors> {
@@ -12,10 +16,56 @@ pub fn color_from_name(name: &str) -> Option<DynColors> {
         "purple" | "magenta" => Some(DynColors::Rgb(255, 0, 255)),
         "cyan" => Some(DynColors::Rgb(0, 255, 255)),
         "white" => Some(DynColors::Rgb(255, 255, 255)),
-        _ => None,
+        // Bright variants are the fixed (256-color) codes 8-15, so they follow
+        // the terminal's palette
+        "bright_black" | "gray" | "grey" => Some(DynColors::Xterm(XtermColors::from(8))),
+        "bright_red" => Some(DynColors::Xterm(XtermColors::from(9))),
+        "bright_green" => Some(DynColors::Xterm(XtermColors::from(10))),
+        "bright_yellow" => Some(DynColors::Xterm(XtermColors::from(11))),
+        "bright_blue" => Some(DynColors::Xterm(XtermColors::from(12))),
+        "bright_magenta" | "bright_purple" => Some(DynColors::Xterm(XtermColors::from(13))),
+        "bright_cyan" => Some(DynColors::Xterm(XtermColors::from(14))),
+        "bright_white" => Some(DynColors::Xterm(XtermColors::from(15))),
+        _ => color_from_fixed(name),
+    }
+}
+
+/// Parse a 256-color palette index, bare (`201`) or as `fixed:201`. Unlike
+/// `r,g,b` entries these never contain commas
+pub fn color_from_fixed(entry: &str) -> Option<DynColors> {
+    let index = entry.strip_prefix("fixed:").unwrap_or(entry).trim();
+    let index = index.parse::<u8>().ok()?;
+    Some(DynColors::Xterm(XtermColors::from(index)))
+}
+
+/// A color entry that's neither a known name, a palette index nor RGB/hex
+#[derive(Debug, PartialEq)]
+pub struct UnknownColor(pub String);
+
+impl fmt::Display for UnknownColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown color '{}'", self.0)
     }
 }
 
+/// Resolve a `COLOR` entry: a name or palette index, or failing that an
+/// RGB/hex color
+pub fn resolve_color(entry: &str) -> Result<DynColors, UnknownColor> {
+    color_from_name(entry)
+        .or_else(|| color_from_rgb_entry(entry))
+        .ok_or_else(|| UnknownColor(entry.to_string()))
+}
+
+/// Configured `COLOR` and `BG_COLOR` entries that don't resolve
+pub fn unknown_colors(config: &ConfigMommy) -> Vec<UnknownColor> {
+    config
+        .colors
+        .iter()
+        .chain(&config.bg_color)
+        .filter_map(|entry| resolve_color(entry).err())
+        .collect()
+}
+
 #[inline]
 pub fn color_from_rgb(rgb_str: &str) -> Option<DynColors> {
     let mut parts = rgb_str.split(',').map(str::trim);
@@ -30,7 +80,144 @@ pub fn color_from_rgb(rgb_str: &str) -> Option<DynColors> {
     Some(DynColors::Rgb(r, g, b))
 }
 
-/// Apply a single style attribute to the Style object
+/// Parse `#rrggbb`, `rrggbb` or the short `#rgb` form, case-insensitively
+pub fn color_from_hex(hex_str: &str) -> Option<DynColors> {
+    let hex = hex_str.trim();
+    let hex = hex.strip_prefix('#').unwrap_or(hex);
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+    match hex.len() {
+        6 => Some(DynColors::Rgb(
+            channel(&hex[0..2])?,
+            channel(&hex[2..4])?,
+            channel(&hex[4..6])?,
+        )),
+        // Each digit is doubled: #f80 is #ff8800
+        3 => Some(DynColors::Rgb(
+            channel(&hex[0..1])? * 17,
+            channel(&hex[1..2])? * 17,
+            channel(&hex[2..3])? * 17,
+        )),
+        _ => None,
+    }
+}
+
+/// Parse a `COLOR_RGB` entry: hex (`#ff8800`, `ff8800`, `#f80`) or `r,g,b`
+pub fn color_from_rgb_entry(entry: &str) -> Option<DynColors> {
+    let trimmed = entry.trim();
+    let is_hex = trimmed.starts_with('#')
+        || (trimmed.len() == 6 && trimmed.chars().all(|c| c.is_ascii_hexdigit()));
+    if is_hex {
+        color_from_hex(trimmed)
+    } else {
+        color_from_rgb(trimmed)
+    }
+}
+
+/// Parse a `GRADIENT` value: two RGB entries (`r,g,b` or hex) separated by
+/// a colon, like "255,0,0:0,0,255"
+pub fn parse_gradient(value: &str) -> Option<(Rgb, Rgb)> {
+    let rgb = |entry: &str| match color_from_rgb_entry(entry)? {
+        DynColors::Rgb(r, g, b) => Some(Rgb(r, g, b)),
+        _ => None,
+    };
+    let (start, end) = value.split_once(':')?;
+    Some((rgb(start)?, rgb(end)?))
+}
+
+/// Color every character of `text` with its own RGB color, blending from
+/// `start` on the first character to `end` on the last
+pub fn paint_gradient(text: &str, start: Rgb, end: Rgb) -> String {
+    let steps = text.chars().count().saturating_sub(1);
+    text.chars()
+        .enumerate()
+        .map(|(i, c)| c.color(blend_rgb(start, end, i, steps)).to_string())
+        .collect()
+}
+
+/// The color `i` steps of `steps` along the way from `start` to `end`
+fn blend_rgb(start: Rgb, end: Rgb, i: usize, steps: usize) -> Rgb {
+    let steps = steps.max(1) as i64;
+    let blend = |from: u8, to: u8| {
+        let (from, to) = (i64::from(from), i64::from(to));
+        // i <= steps keeps the result between from and to
+        u8::try_from(from + (to - from) * i as i64 / steps).unwrap_or_default()
+    };
+    Rgb(
+        blend(start.0, end.0),
+        blend(start.1, end.1),
+        blend(start.2, end.2),
+    )
+}
+
+/// Spread a gradient over the emotes of a line instead of its characters:
+/// each emote gets one color, from `start` on the first to `end` on the last,
+/// and the text around them keeps `style`. Emotes are matched whole, so an
+/// emoji made of several characters isn't split up. `None` with fewer than
+/// two emotes in the line
+pub fn paint_emote_gradient(
+    text: &str,
+    emotes: &[String],
+    start: Rgb,
+    end: Rgb,
+    style: Style,
+) -> Option<String> {
+    // Where each emote sits, preferring the longest match at a position
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while i < text.len() {
+        let emote = emotes
+            .iter()
+            .filter(|emote| !emote.is_empty() && text[i..].starts_with(emote.as_str()))
+            .max_by_key(|emote| emote.len());
+        match emote {
+            Some(emote) => {
+                spans.push((i, i + emote.len()));
+                i += emote.len();
+            }
+            None => i += text[i..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    if spans.len() < 2 {
+        return None;
+    }
+
+    let steps = spans.len() - 1;
+    let mut painted = String::with_capacity(text.len() * 2);
+    let mut last_end = 0;
+    for (n, &(from, to)) in spans.iter().enumerate() {
+        let Rgb(r, g, b) = blend_rgb(start, end, n, steps);
+        if from > last_end {
+            painted.push_str(&style.style(&text[last_end..from]).to_string());
+        }
+        painted.push_str(&style.truecolor(r, g, b).style(&text[from..to]).to_string());
+        last_end = to;
+    }
+    if last_end < text.len() {
+        painted.push_str(&style.style(&text[last_end..]).to_string());
+    }
+    Some(painted)
+}
+
+/// Style attributes understood in `STYLE`
+pub const STYLE_ATTRS: &[&str] = &[
+    "bold",
+    "italic",
+    "dimmed",
+    "underline",
+    "blink",
+    "reverse",
+    "hidden",
+    "strikethrough",
+    "strike",
+];
+
+/// Apply a single style attribute to the Style object. Supported names are
+/// `bold`, `italic`, `dimmed`, `underline`, `blink`, `reverse`, `hidden` and
+/// `strikethrough` (or `strike`); anything else is ignored
 fn apply_style_attr(mut style: Style, attr: &str) -> Style {
     match attr {
         "bold" => style = style.bold(),
@@ -40,32 +227,205 @@ fn apply_style_attr(mut style: Style, attr: &str) -> Style {
         "blink" => style = style.blink(),
         "reverse" => style = style.reversed(),
         "hidden" => style = style.hidden(),
+        "strikethrough" | "strike" => style = style.strikethrough(),
         _ => {}
     }
     style
 }
 
-pub fn random_style_pick(config: &ConfigMommy) -> Style {
-    let mut style = Style::new();
+/// Random subset of all attributes across the style combos, each kept with
+/// even odds. At least one is kept whenever there are any
+fn mix_style_attrs(styles: &[Vec<String>]) -> Vec<&str> {
+    let mut all: Vec<&str> = Vec::new();
+    for attr in styles.iter().flatten() {
+        if !all.contains(&attr.as_str()) {
+            all.push(attr);
+        }
+    }
+    let mut picked: Vec<&str> = all.iter().copied().filter(|_| random::bool()).collect();
+    if picked.is_empty() && !all.is_empty() {
+        picked.push(all[random::usize(..all.len())]);
+    }
+    picked
+}
 
-    // Use pre-parsed color vectors from config
-    if let Some(ref rgb_candidates) = config.color_rgb {
-        if !rgb_candidates.is_empty() {
-            let idx = fastrand::usize(..rgb_candidates.len());
-            if let Some(col) = color_from_rgb(&rgb_candidates[idx]) {
-                style = style.color(col);
-            }
+/// Colors to pick from, given named and RGB entries. RGB entries win over
+/// named ones unless `merge` asks for both in one pool
+fn candidates(names: &[String], rgb: Option<&Vec<String>>, merge: bool) -> Vec<DynColors> {
+    let named = names.iter().filter_map(|name| resolve_color(name).ok());
+    let rgb_colors = rgb
+        .into_iter()
+        .flatten()
+        .filter_map(|rgb| color_from_rgb_entry(rgb));
+
+    if merge {
+        named.chain(rgb_colors).collect()
+    } else if rgb.is_some() {
+        rgb_colors.collect()
+    } else {
+        named.collect()
+    }
+}
+
+/// Colors to pick from. `COLOR_RGB` wins over `COLOR` unless `COLOR_MERGE`
+/// asks for both in one pool. If none of them resolve, the default white is
+/// used
+fn color_candidates(config: &ConfigMommy) -> Vec<DynColors> {
+    let colors = candidates(
+        &config.colors,
+        config.color_rgb.as_ref(),
+        config.color_merge,
+    );
+    if colors.is_empty() {
+        vec![DynColors::Rgb(255, 255, 255)]
+    } else {
+        colors
+    }
+}
+
+/// Background colors to pick from, `BG_COLOR_RGB` and `BG_COLOR` combined the
+/// same way as the foreground ones
+fn bg_color_candidates(config: &ConfigMommy) -> Vec<DynColors> {
+    candidates(
+        &config.bg_color,
+        config.bg_color_rgb.as_ref(),
+        config.color_merge,
+    )
+}
+
+/// Color for a mood's lines: the mood's own color, unless the user set
+/// `COLOR` or `COLOR_RGB`, which always wins
+pub fn effective_color(config: &ConfigMommy, mood_color: Option<DynColors>) -> Option<DynColors> {
+    mood_color.filter(|_| !config.colors_explicit)
+}
+
+/// Style an operational message (warning, error) in the warn color, or leave
+/// it plain when colors are off
+pub fn style_warning(message: &str, color: Option<DynColors>) -> String {
+    match color {
+        Some(color) => message.style(Style::new().color(color)).to_string(),
+        None => message.to_string(),
+    }
+}
+
+/// `--color` choice on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorMode {
+    Always,
+    Never,
+    #[default]
+    Auto,
+}
+
+impl ColorMode {
+    /// Explicit choice to pass to `should_colorize`, `None` to detect
+    pub fn force(self) -> Option<bool> {
+        match self {
+            Self::Always => Some(true),
+            Self::Never => Some(false),
+            Self::Auto => None,
         }
-    } else if !config.colors.is_empty() {
-        let idx = fastrand::usize(..config.colors.len());
-        if let Some(col) = color_from_name(&config.colors[idx]) {
-            style = style.color(col);
+    }
+}
+
+/// The color conventions other tools share through the environment
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ColorEnv {
+    // `NO_COLOR` set to anything non-empty
+    pub no_color: bool,
+    // `CLICOLOR_FORCE` set to anything but `0`
+    pub clicolor_force: bool,
+    // `CLICOLOR=0`
+    pub clicolor_off: bool,
+}
+
+impl ColorEnv {
+    pub fn from_env() -> Self {
+        let var = |name| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Self {
+            no_color: var("NO_COLOR").is_some(),
+            clicolor_force: var("CLICOLOR_FORCE").is_some_and(|v| v != "0"),
+            clicolor_off: var("CLICOLOR").is_some_and(|v| v == "0"),
         }
     }
+}
+
+/// Whether to color output, first match wins: an explicit `--color` choice,
+/// `NO_COLOR` (off), `CLICOLOR_FORCE` (on), `CLICOLOR=0` (off), and otherwise
+/// only when writing to a terminal
+pub fn should_colorize(is_tty: bool, env: ColorEnv, force: Option<bool>) -> bool {
+    force.unwrap_or(if env.no_color {
+        false
+    } else if env.clicolor_force {
+        true
+    } else if env.clicolor_off {
+        false
+    } else {
+        is_tty
+    })
+}
+
+/// `should_colorize` for stderr, where mommy prints
+pub fn colorize_stderr(force: Option<bool>) -> bool {
+    should_colorize(io::stderr().is_terminal(), ColorEnv::from_env(), force)
+}
+
+/// `should_colorize` for the stream affirmations go to
+pub fn colorize_output(config: &ConfigMommy) -> bool {
+    should_colorize(
+        config.output.is_terminal(),
+        ColorEnv::from_env(),
+        config.force_color,
+    )
+}
+
+/// Random style for an affirmation, or a plain one when colors are off. The
+/// style is picked either way so the random sequence doesn't depend on it
+pub fn output_style(config: &ConfigMommy, mood_color: Option<DynColors>) -> Style {
+    let style = random_style_pick(config, mood_color);
+    if colorize_output(config) {
+        style
+    } else {
+        Style::new()
+    }
+}
+
+/// Pick a color and style for one line from `config`, regardless of whether
+/// the terminal wants colors
+///
+/// ```
+/// use owo_colors::OwoColorize;
+///
+/// let config = shell_mommy::load_config();
+/// let style = shell_mommy::random_style_pick(&config, None);
+/// println!("{}", "good job~".style(style));
+/// ```
+pub fn random_style_pick(config: &ConfigMommy, mood_color: Option<DynColors>) -> Style {
+    let mut style = Style::new();
+
+    // Use the mood's color if it applies, else the pre-parsed color vectors
+    // from config
+    let colors = color_candidates(config);
+    if let Some(color) = effective_color(config, mood_color) {
+        style = style.color(color);
+    } else if !colors.is_empty() {
+        style = style.color(colors[random::usize(..colors.len())]);
+    }
 
-    // Use pre-parsed style combinations from config
-    if !config.styles.is_empty() {
-        let idx = fastrand::usize(..config.styles.len());
+    // Background stays unset unless BG_COLOR/BG_COLOR_RGB is configured
+    let bg_colors = bg_color_candidates(config);
+    if !bg_colors.is_empty() {
+        style = style.on_color(bg_colors[random::usize(..bg_colors.len())]);
+    }
+
+    // Use pre-parsed style combinations from config, or with STYLE_MIX a
+    // random mix of every listed attribute
+    if config.style_mix {
+        for attr in mix_style_attrs(&config.styles) {
+            style = apply_style_attr(style, attr);
+        }
+    } else if !config.styles.is_empty() {
+        let idx = random::usize(..config.styles.len());
         let styles_in_combo = &config.styles[idx];
 
         // Styles are already parsed, just apply them
@@ -80,7 +440,10 @@ pub fn random_style_pick(config: &ConfigMommy) -> Style {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::load_config;
+    use crate::{
+        affirmations::load_custom_affirmations_with_mood_mixing,
+        config::{load_config, use_empty_config_dir, ENV_TEST_LOCK},
+    };
 
     #[test]
     fn test_color_names() {
@@ -102,6 +465,94 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_bright_color_names() {
+        // Make sure all bright colors map to their fixed codes:
+        let fixed = |code| Some(DynColors::Xterm(XtermColors::from(code)));
+        assert_eq!(color_from_name("bright_black"), fixed(8));
+        assert_eq!(color_from_name("gray"), fixed(8));
+        assert_eq!(color_from_name("grey"), fixed(8));
+        assert_eq!(color_from_name("bright_red"), fixed(9));
+        assert_eq!(color_from_name("bright_green"), fixed(10));
+        assert_eq!(color_from_name("bright_yellow"), fixed(11));
+        assert_eq!(color_from_name("bright_blue"), fixed(12));
+        assert_eq!(color_from_name("bright_magenta"), fixed(13));
+        assert_eq!(color_from_name("bright_purple"), fixed(13));
+        assert_eq!(color_from_name("bright_cyan"), fixed(14));
+        assert_eq!(color_from_name("bright_white"), fixed(15));
+    }
+
+    #[test]
+    fn test_fixed_colors() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        use_empty_config_dir();
+        use owo_colors::OwoColorize;
+
+        // Expect: both forms pick from the 256-color palette
+        assert_eq!(
+            color_from_name("201"),
+            Some(DynColors::Xterm(XtermColors::from(201)))
+        );
+        let fixed = color_from_name("fixed:160").unwrap();
+        let output = "Test".style(Style::new().color(fixed)).to_string();
+        assert!(output.starts_with("\x1b[38;5;160m"), "got {output:?}");
+
+        // Expect: out of range or malformed indices are skipped
+        assert_eq!(color_from_name("256"), None);
+        assert_eq!(color_from_name("fixed:-1"), None);
+        assert_eq!(color_from_name("fixed:"), None);
+        assert_eq!(color_from_name("1,2,3"), None);
+
+        // Expect: they go through the random pick like named colors
+        let mut config = load_config();
+        config.colors = vec!["fixed:42".to_string()];
+        config.color_rgb = None;
+        config.colors_explicit = true;
+        config.styles = vec![];
+        assert_eq!(
+            random_style_pick(&config, None),
+            Style::new().color(DynColors::Xterm(XtermColors::from(42)))
+        );
+    }
+
+    #[test]
+    fn test_resolve_color() {
+        assert_eq!(resolve_color("red"), Ok(DynColors::Rgb(255, 0, 0)));
+        assert_eq!(resolve_color("#00ff00"), Ok(DynColors::Rgb(0, 255, 0)));
+        assert_eq!(
+            resolve_color("reddish"),
+            Err(UnknownColor("reddish".to_string()))
+        );
+        assert_eq!(
+            UnknownColor("reddish".to_string()).to_string(),
+            "unknown color 'reddish'"
+        );
+    }
+
+    #[test]
+    fn test_unknown_colors() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        use_empty_config_dir();
+        let mut config = load_config();
+        config.bg_color = vec![];
+
+        // Expect: a bogus name is reported, falling back to white
+        config.colors = vec!["reddish".to_string()];
+        config.color_rgb = None;
+        assert_eq!(
+            unknown_colors(&config),
+            vec![UnknownColor("reddish".to_string())]
+        );
+        assert_eq!(
+            color_candidates(&config),
+            vec![DynColors::Rgb(255, 255, 255)]
+        );
+
+        // Expect: valid names aren't
+        config.colors = vec!["red".to_string(), "fixed:9".to_string()];
+        assert!(unknown_colors(&config).is_empty());
+    }
+
     #[test]
     fn test_invalid_color() {
         // Not valid color name:
@@ -133,8 +584,43 @@ mod tests {
         assert_eq!(color_from_rgb("256,0,0"), None);
     }
 
+    #[test]
+    fn test_hex_color() {
+        // Long, bare and short forms, any case, with whitespace:
+        assert_eq!(color_from_hex("#ff8800"), Some(DynColors::Rgb(255, 136, 0)));
+        assert_eq!(color_from_hex("FF8800"), Some(DynColors::Rgb(255, 136, 0)));
+        assert_eq!(color_from_hex(" #F80 "), Some(DynColors::Rgb(255, 136, 0)));
+
+        // Wrong length or non-hex characters:
+        assert_eq!(color_from_hex("#ff88"), None);
+        assert_eq!(color_from_hex("#ff880000"), None);
+        assert_eq!(color_from_hex("#gg8800"), None);
+        assert_eq!(color_from_hex("#"), None);
+    }
+
+    #[test]
+    fn test_rgb_entry_routing() {
+        // Hex entries go through the hex parser, comma ones through the RGB one:
+        assert_eq!(
+            color_from_rgb_entry("#ff8800"),
+            Some(DynColors::Rgb(255, 136, 0))
+        );
+        assert_eq!(
+            color_from_rgb_entry("ff8800"),
+            Some(DynColors::Rgb(255, 136, 0))
+        );
+        assert_eq!(
+            color_from_rgb_entry("255,136,0"),
+            Some(DynColors::Rgb(255, 136, 0))
+        );
+        assert_eq!(color_from_rgb_entry("#ff88zz"), None);
+        assert_eq!(color_from_rgb_entry("255,136"), None);
+    }
+
     #[test]
     fn test_color_style() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        use_empty_config_dir();
         use owo_colors::OwoColorize;
 
         // Not RGB and bold:
@@ -143,7 +629,7 @@ mod tests {
         config.styles = vec![vec!["bold".to_string()]];
         config.color_rgb = None;
 
-        let styled = random_style_pick(&config);
+        let styled = random_style_pick(&config, None);
         let output = "Test".style(styled).to_string();
 
         // Check that output contains ANSI escape codes
@@ -156,6 +642,8 @@ mod tests {
 
     #[test]
     fn test_rgb_with_two_styles() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        use_empty_config_dir();
         use owo_colors::OwoColorize;
 
         // RGB and two styles:
@@ -163,7 +651,7 @@ mod tests {
         config.styles = vec![vec!["underline".to_string(), "italic".to_string()]];
         config.color_rgb = Some(vec!["128,0,255".to_string()]);
 
-        let styled = random_style_pick(&config);
+        let styled = random_style_pick(&config, None);
         let output = "Test".style(styled).to_string();
 
         // Check that output contains ANSI escape codes and RGB color codes
@@ -173,4 +661,289 @@ mod tests {
         );
         assert!(output.contains("Test"), "expected output to contain 'Test'");
     }
+
+    #[test]
+    fn test_strikethrough_style() {
+        use owo_colors::OwoColorize;
+
+        // Expect: SGR 9, under either name
+        let styled = |attr| {
+            "Test"
+                .style(apply_style_attr(Style::new(), attr))
+                .to_string()
+        };
+        assert_eq!(styled("strikethrough"), "\x1b[9mTest\x1b[0m");
+        assert_eq!(styled("strike"), styled("strikethrough"));
+    }
+
+    #[test]
+    fn test_parse_gradient() {
+        assert_eq!(
+            parse_gradient("255,0,0:0,0,255"),
+            Some((Rgb(255, 0, 0), Rgb(0, 0, 255)))
+        );
+        assert_eq!(
+            parse_gradient("#ff0000:00ff00"),
+            Some((Rgb(255, 0, 0), Rgb(0, 255, 0)))
+        );
+        assert_eq!(parse_gradient("255,0,0"), None);
+        assert_eq!(parse_gradient("red:blue"), None);
+    }
+
+    #[test]
+    fn test_paint_gradient() {
+        let painted = paint_gradient("héllo~💖", Rgb(255, 0, 0), Rgb(0, 0, 255));
+
+        // Expect: start color on the first character, end color on the last,
+        // and a blend in between, multibyte characters included
+        assert!(painted.starts_with("\x1b[38;2;255;0;0mh\x1b[39m"));
+        assert!(painted.ends_with("\x1b[38;2;0;0;255m💖\x1b[39m"));
+        assert!(painted.contains("é"));
+        assert_eq!(painted.matches("\x1b[38;2;").count(), 7);
+
+        // Expect: a single character gets the start color
+        assert_eq!(
+            paint_gradient("x", Rgb(1, 2, 3), Rgb(4, 5, 6)),
+            "\x1b[38;2;1;2;3mx\x1b[39m"
+        );
+        assert_eq!(paint_gradient("", Rgb(1, 2, 3), Rgb(4, 5, 6)), "");
+    }
+
+    #[test]
+    fn test_paint_emote_gradient() {
+        let emotes = vec!["💖".to_string(), "🏳️‍🌈".to_string(), "✨".to_string()];
+        let (start, end) = (Rgb(255, 0, 0), Rgb(0, 0, 255));
+        let painted =
+            paint_emote_gradient("good girl 💖 🏳️‍🌈 ✨~", &emotes, start, end, Style::new()).unwrap();
+
+        // Expect: with 3 emotes the first and last carry the endpoint colors,
+        // the middle one the blend, and the text isn't colored
+        assert!(painted.starts_with("good girl "));
+        assert!(painted.contains("\x1b[38;2;255;0;0m💖\x1b[0m"));
+        assert!(painted.contains("\x1b[38;2;128;0;127m🏳️‍🌈\x1b[0m"));
+        assert!(painted.contains("\x1b[38;2;0;0;255m✨\x1b[0m"));
+        assert!(painted.ends_with('~'));
+
+        // Expect: a multi-character emoji keeps a single color
+        assert_eq!(painted.matches("\x1b[38;2;").count(), 3);
+
+        // Expect: fewer than two emotes leaves it to the per-character gradient
+        assert_eq!(
+            paint_emote_gradient("just 💖~", &emotes, start, end, Style::new()),
+            None
+        );
+        assert_eq!(
+            paint_emote_gradient("💖💖", &[String::new()], start, end, Style::new()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_rgb_background() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        use_empty_config_dir();
+        use owo_colors::OwoColorize;
+
+        let mut config = load_config();
+        config.colors = vec!["red".to_string()];
+        config.color_rgb = None;
+        config.styles = vec![vec!["bold".to_string()]];
+        config.bg_color = vec!["blue".to_string()];
+        config.bg_color_rgb = Some(vec!["10,20,30".to_string()]);
+
+        // Expect: the RGB background composes with the foreground and style
+        let output = "Test".style(random_style_pick(&config, None)).to_string();
+        assert!(output.contains("48;2;10;20;30"), "got {output:?}");
+        assert!(output.contains("38;2;255;0;0"), "got {output:?}");
+        assert!(output.contains(";1m"), "got {output:?}");
+
+        // Expect: no background by default
+        config.bg_color = vec![];
+        config.bg_color_rgb = None;
+        let output = "Test".style(random_style_pick(&config, None)).to_string();
+        assert!(!output.contains("48;"), "got {output:?}");
+    }
+
+    #[test]
+    fn test_named_colors_random_pick() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        use_empty_config_dir();
+        let mut config = load_config();
+        config.colors = vec!["red".to_string(), "green".to_string(), "blue".to_string()];
+        config.color_rgb = None;
+        config.colors_explicit = true;
+        config.bg_color = vec![];
+        config.bg_color_rgb = None;
+        config.styles = vec![];
+
+        // Expect: each run picks one of the listed colors, not always the same
+        fastrand::seed(3);
+        let picks: Vec<Style> = (0..20).map(|_| random_style_pick(&config, None)).collect();
+        let expected = [
+            DynColors::Rgb(255, 0, 0),
+            DynColors::Rgb(0, 255, 0),
+            DynColors::Rgb(0, 0, 255),
+        ]
+        .map(|color| Style::new().color(color));
+        assert!(picks.iter().all(|pick| expected.contains(pick)));
+        assert!(picks.iter().any(|pick| *pick != picks[0]));
+
+        // Expect: the same seed gives the same picks
+        fastrand::seed(3);
+        let again: Vec<Style> = (0..20).map(|_| random_style_pick(&config, None)).collect();
+        assert_eq!(picks, again);
+    }
+
+    #[test]
+    fn test_mix_style_attrs() {
+        let styles = vec![
+            vec!["bold".to_string(), "italic".to_string()],
+            vec!["underline".to_string(), "bold".to_string()],
+        ];
+
+        // Expect: every mix is a non-empty set of listed attributes, not
+        // always the same one
+        fastrand::seed(7);
+        let mixes: Vec<Vec<&str>> = (0..20).map(|_| mix_style_attrs(&styles)).collect();
+        for mix in &mixes {
+            assert!(!mix.is_empty());
+            assert!(mix
+                .iter()
+                .all(|attr| ["bold", "italic", "underline"].contains(attr)));
+            assert!(mix.len() <= 3);
+        }
+        assert!(mixes.iter().any(|mix| *mix != mixes[0]));
+
+        // Expect: the same seed gives the same mixes
+        fastrand::seed(7);
+        let again: Vec<Vec<&str>> = (0..20).map(|_| mix_style_attrs(&styles)).collect();
+        assert_eq!(mixes, again);
+
+        // Expect: nothing to mix without styles
+        assert!(mix_style_attrs(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_color_candidates() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        use_empty_config_dir();
+        let mut config = load_config();
+        config.colors = vec!["red".to_string(), "not a color".to_string()];
+        config.color_rgb = Some(vec!["0,0,128".to_string()]);
+
+        // Expect: RGB colors win over named ones by default
+        config.color_merge = false;
+        assert_eq!(color_candidates(&config), vec![DynColors::Rgb(0, 0, 128)]);
+
+        // Expect: merging pools both, skipping invalid entries
+        config.color_merge = true;
+        assert_eq!(
+            color_candidates(&config),
+            vec![DynColors::Rgb(255, 0, 0), DynColors::Rgb(0, 0, 128)]
+        );
+
+        // Expect: named colors without any RGB ones
+        config.color_merge = false;
+        config.color_rgb = None;
+        assert_eq!(color_candidates(&config), vec![DynColors::Rgb(255, 0, 0)]);
+    }
+
+    #[test]
+    fn test_mood_color() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        use_empty_config_dir();
+        let path =
+            std::env::temp_dir().join(format!("mommy-test-mood-color-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r##"{"moods": {
+                "chill": {"color": "cyan", "positive": ["a"], "negative": ["b"]},
+                "ominous": {"color": "cyan", "color_rgb": "#8b0000", "positive": ["c"], "negative": ["d"]}
+            }}"##,
+        )
+        .unwrap();
+        let mood_color = |mood| {
+            load_custom_affirmations_with_mood_mixing(&path, mood, false, 4096, &mut Vec::new())
+                .unwrap()
+                .color()
+        };
+        let chill = mood_color("chill");
+        let ominous = mood_color("ominous");
+        std::fs::remove_file(&path).unwrap();
+
+        // Expect: color_rgb wins over color within a mood
+        assert_eq!(chill, Some(DynColors::Rgb(0, 255, 255)));
+        assert_eq!(ominous, Some(DynColors::Rgb(139, 0, 0)));
+
+        let mut config = load_config();
+        config.styles = vec![];
+        config.colors = vec!["red".to_string()];
+
+        // Expect: the mood's color is used while COLOR isn't set
+        config.colors_explicit = false;
+        assert_eq!(effective_color(&config, ominous), ominous);
+        assert_eq!(
+            random_style_pick(&config, ominous),
+            Style::new().color(DynColors::Rgb(139, 0, 0))
+        );
+        assert_eq!(effective_color(&config, None), None);
+
+        // Expect: an explicit COLOR wins over the mood's color
+        config.colors_explicit = true;
+        assert_eq!(effective_color(&config, ominous), None);
+        assert_eq!(
+            random_style_pick(&config, ominous),
+            Style::new().color(DynColors::Rgb(255, 0, 0))
+        );
+    }
+
+    #[test]
+    fn test_should_colorize() {
+        let env = |no_color, clicolor_force, clicolor_off| ColorEnv {
+            no_color,
+            clicolor_force,
+            clicolor_off,
+        };
+
+        // Expect: colors on a terminal unless NO_COLOR is set
+        assert!(should_colorize(true, env(false, false, false), None));
+        assert!(!should_colorize(true, env(true, false, false), None));
+        assert!(!should_colorize(false, env(false, false, false), None));
+        assert!(!should_colorize(false, env(true, false, false), None));
+
+        // Expect: CLICOLOR=0 turns them off on a terminal
+        assert!(!should_colorize(true, env(false, false, true), None));
+        assert!(!should_colorize(false, env(false, false, true), None));
+
+        // Expect: CLICOLOR_FORCE turns them on anywhere, over CLICOLOR=0 too
+        assert!(should_colorize(false, env(false, true, false), None));
+        assert!(should_colorize(true, env(false, true, false), None));
+        assert!(should_colorize(false, env(false, true, true), None));
+
+        // Expect: NO_COLOR wins over CLICOLOR_FORCE
+        assert!(!should_colorize(true, env(true, true, false), None));
+        assert!(!should_colorize(false, env(true, true, true), None));
+
+        // Expect: an explicit choice wins over everything
+        for is_tty in [true, false] {
+            for bits in 0..8 {
+                let vars = env(bits & 1 != 0, bits & 2 != 0, bits & 4 != 0);
+                assert!(should_colorize(is_tty, vars, Some(true)));
+                assert!(!should_colorize(is_tty, vars, Some(false)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_style_warning() {
+        let yellow = color_from_name("yellow");
+
+        // Expect: the warn color's escape codes around the message
+        let styled = style_warning("careful~", yellow);
+        assert!(styled.starts_with("\x1b[38;2;255;255;0m"));
+        assert!(styled.contains("careful~"));
+
+        // Expect: the message untouched without colors
+        assert_eq!(style_warning("careful~", None), "careful~");
+    }
 }
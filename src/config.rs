@@ -1,4 +1,40 @@
-use std::{env, path::PathBuf};
+use crate::{
+    affirmations::{
+        affirmation_texts, Branch, DEFAULT_MAX_AFFIRMATIONS_BYTES, EMBEDDED_AFFIRMATIONS_JSON,
+    },
+    color::{color_from_name, color_from_rgb_entry},
+    state::{config_dir, Scope},
+    utils::Output,
+};
+use owo_colors::DynColors;
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env, fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// `config.toml` written by `mommy --init`, every setting commented out so it
+/// changes nothing until edited
+pub const STARTER_CONFIG: &str = r#"# mommy's config file. Keys are the SHELL_MOMMYS_* env var names without the
+# prefix, in lowercase. Environment variables still win over this file.
+
+# pronouns = "her"
+# roles = "mommy"
+# little = ["girl", "kitten"]
+# emotes = ["❤️", "💖", "💗", "💓", "💞"]
+# moods = ["chill"]
+# color = ["white"]
+# style = ["bold"]
+# only_negative = false
+# needy = false
+# chance = 1.0
+"#;
+
+/// How deep mommys may nest before giving up, unless `RECURSION_MAX` says
+/// otherwise
+pub const DEFAULT_RECURSION_MAX: usize = 100;
 
 /// Cached binary information to avoid redundant filesystem calls
 #[derive(Debug, Clone)]
@@ -17,24 +53,41 @@ impl BinaryInfo {
             .unwrap_or("mommy")
             .to_string();
 
-        let is_cargo_subcommand = name.starts_with("cargo-");
-
-        // Handle both "cargo-mommy", "cargo-daddy" and plain "mommy", "daddy"
-        let stripped = name.strip_prefix("cargo-").unwrap_or(&name);
-        let role = if stripped.contains("daddy") {
-            "daddy".to_string()
-        } else {
-            "mommy".to_string()
-        };
-
         BinaryInfo {
+            role: detect_role_from_binary(&name),
+            is_cargo_subcommand: is_cargo_subcommand(&name),
             path,
-            role,
-            is_cargo_subcommand,
         }
     }
 }
 
+/// Binary file name without a trailing `.exe` (any case), lowercased since
+/// Windows doesn't care about case either
+fn binary_stem(name: &str) -> String {
+    let name = name.to_lowercase();
+    match name.strip_suffix(".exe") {
+        Some(stem) => stem.to_string(),
+        None => name,
+    }
+}
+
+/// Whether a binary called `name` runs as a cargo subcommand (`cargo-*`)
+fn is_cargo_subcommand(name: &str) -> bool {
+    binary_stem(name).starts_with("cargo-")
+}
+
+/// Role for a binary called `name`. Handles both "cargo-mommy", "cargo-daddy"
+/// and plain "mommy", "daddy"
+fn detect_role_from_binary(name: &str) -> String {
+    let stem = binary_stem(name);
+    let stripped = stem.strip_prefix("cargo-").unwrap_or(&stem);
+    if stripped.contains("daddy") {
+        "daddy".to_string()
+    } else {
+        "mommy".to_string()
+    }
+}
+
 #[derive(Debug)]
 pub struct ConfigMommy {
     // Pre-parsed string options for efficient random selection
@@ -42,29 +95,88 @@ pub struct ConfigMommy {
     pub roles: Vec<String>,
     pub little: Vec<String>,
     pub emotes: Vec<String>,
+    // Relative weights for the lists above (`mommy*4/daddy`), uniform when
+    // they don't line up with the values
+    pub role_weights: Vec<u32>,
+    pub pronoun_weights: Vec<u32>,
+    pub little_weights: Vec<u32>,
+    pub emote_weights: Vec<u32>,
+    pub no_emotes: bool,
     pub moods: Vec<String>,
+    pub moods_explicit: bool,
+    pub mood_fallback: Vec<String>,
 
-    // Pre-parsed color options
+    // Pre-parsed color options. `COLOR` takes a slash list like
+    // "red/green/blue", one of which is picked per run
     pub colors: Vec<String>,
+    pub colors_explicit: bool,
     pub color_rgb: Option<Vec<String>>,
+    pub color_merge: bool,
+    pub bg_color: Vec<String>,
+    pub bg_color_rgb: Option<Vec<String>>,
+    pub gradient: Option<String>,
+    pub warn_color: DynColors,
+    pub force_color: Option<bool>,
+    pub json_output: bool,
+    pub output: Output,
 
     // Pre-parsed style combinations (each is a Vec of style attributes)
     pub styles: Vec<Vec<String>>,
+    pub style_mix: bool,
 
     pub aliases: Option<String>,
+    pub aliases_shell: bool,
     pub affirmations: Option<String>,
+    pub affirmations_merge: bool,
+    pub pack: Option<String>,
+    pub max_affirmations_bytes: u64,
+    pub chance: f32,
     pub needy: bool,
     pub only_negative: bool,
+    pub only_positive: bool,
     pub quiet: bool,
-    pub recursion_limit: usize,
+    pub begging: bool,
+    pub force_branch: Option<Branch>,
+    pub interrupted: bool,
+    pub capture: bool,
+    pub timeout: Option<Duration>,
+    pub timed_out: bool,
+    pub last_stderr: Option<String>,
+    pub warn_count: usize,
+    pub error_count: usize,
+    pub recursion_depth: usize,
+    pub recursion_max: usize,
+    pub recursion_quiet: bool,
     pub mood_mixing: bool,
+    pub no_env_propagate: bool,
+    pub mood_prefix: bool,
+    pub chain_seed: Option<u64>,
+    pub ci: bool,
+    pub disable: bool,
+    pub severity_match: bool,
+    pub shell: Option<String>,
+    pub lock_identity: bool,
+    pub transform_anywhere: bool,
+    pub combo: bool,
+    pub usage: Option<String>,
+    pub remember_mood: bool,
+    pub mood_rotate: bool,
+    pub no_repeat: bool,
+    pub scope: Scope,
+    pub exit_map: HashMap<i32, i32>,
+    pub neutral_codes: Vec<i32>,
+    pub personas: Vec<String>,
+    pub persona_strict: bool,
+    pub normalize: bool,
+    pub showcase: bool,
+    pub pager: bool,
 
     // Cached binary info
     pub binary_info: BinaryInfo,
 }
 
 /// Gets the environment variable prefix based on the binary info
-fn get_env_prefix_from_binary(binary_info: &BinaryInfo) -> String {
+pub fn get_env_prefix_from_binary(binary_info: &BinaryInfo) -> String {
     if binary_info.is_cargo_subcommand {
         let role = binary_info.role.to_uppercase();
         format!("CARGO_{role}S")
@@ -89,6 +201,120 @@ fn env_with_fallback(prefix: &str, suffix: &str) -> Option<String> {
         .ok()
 }
 
+/// A value in the config file. Lists become slash-separated strings and
+/// booleans `1`/`0`, like the env vars they stand in for
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ConfigValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    List(Vec<ConfigValue>),
+}
+
+impl ConfigValue {
+    fn into_setting(self) -> String {
+        match self {
+            Self::Bool(b) => if b { "1" } else { "0" }.to_string(),
+            Self::Int(i) => i.to_string(),
+            Self::Float(f) => f.to_string(),
+            Self::Text(s) => s,
+            Self::List(items) => items
+                .into_iter()
+                .map(ConfigValue::into_setting)
+                .collect::<Vec<_>>()
+                .join("/"),
+        }
+    }
+}
+
+/// Settings from a config file, keyed like the env vars without their prefix:
+/// `moods = ["chill", "ominous"]` stands for `SHELL_MOMMYS_MOODS=chill/ominous`.
+/// Every key is optional
+#[derive(Debug, Default)]
+pub struct PartialConfig {
+    values: HashMap<String, String>,
+}
+
+impl PartialConfig {
+    fn get(&self, suffix: &str) -> Option<String> {
+        self.values.get(suffix).cloned()
+    }
+}
+
+/// Parse a config file, as TOML unless the path ends in `.json`
+fn parse_config_file(path: &Path, contents: &str) -> Result<PartialConfig, String> {
+    let values: HashMap<String, ConfigValue> = if path.extension().is_some_and(|ext| ext == "json")
+    {
+        serde_json::from_str(contents).map_err(|e| e.to_string())?
+    } else {
+        toml::from_str(contents).map_err(|e| e.to_string())?
+    };
+    Ok(PartialConfig {
+        values: values
+            .into_iter()
+            .map(|(key, value)| (key.to_uppercase(), value.into_setting()))
+            .collect(),
+    })
+}
+
+/// Read and parse the config file at `path`
+pub fn read_config_file(path: &Path) -> Result<PartialConfig, String> {
+    let contents = fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    parse_config_file(path, &contents).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// Load `config.toml` (or `config.json`) from the config dir. `None` if there
+/// is none; a broken file is reported and skipped
+pub fn load_config_file() -> Option<PartialConfig> {
+    let path = ["config.toml", "config.json"]
+        .iter()
+        .map(|name| config_dir().join(name))
+        .find(|path| path.is_file())?;
+    read_config_file(&path)
+        .inspect_err(|e| eprintln!("mommy: ignoring the config file, {e}"))
+        .ok()
+}
+
+/// First `affirmations.json` (or `affirmations.toml`) in the config dir that
+/// loads, for when `AFFIRMATIONS` isn't set
+pub fn default_affirmations_path() -> Option<PathBuf> {
+    ["affirmations.json", "affirmations.toml"]
+        .iter()
+        .map(|name| config_dir().join(name))
+        .find(|path| {
+            fs::read_to_string(path)
+                .is_ok_and(|contents| affirmation_texts(path, &contents).is_ok())
+        })
+}
+
+/// Write a starter `config.toml` and `affirmations.json` to the config dir.
+/// Existing files (a `config.json` counts as a config too) are kept unless
+/// `force` is set. Returns each path with whether it was written
+pub fn init_config_files(force: bool) -> io::Result<Vec<(PathBuf, bool)>> {
+    let dir = config_dir();
+    fs::create_dir_all(&dir)?;
+    let starters = [
+        (&["config.toml", "config.json"][..], STARTER_CONFIG),
+        (
+            &["affirmations.json", "affirmations.toml"][..],
+            EMBEDDED_AFFIRMATIONS_JSON,
+        ),
+    ];
+    starters
+        .into_iter()
+        .map(|(names, contents)| {
+            let path = dir.join(names[0]);
+            let existing = names.iter().map(|name| dir.join(name)).find(|p| p.exists());
+            match existing {
+                Some(existing) if !force => Ok((existing, false)),
+                _ => fs::write(&path, contents).map(|()| (path, true)),
+            }
+        })
+        .collect()
+}
+
 /// Parse a slash-separated string into a Vec<String>
 /// Trims and lowercases each token, filters empty ones
 fn parse_config_string(s: &str) -> Vec<String> {
@@ -98,95 +324,368 @@ fn parse_config_string(s: &str) -> Vec<String> {
         .collect()
 }
 
+/// Parse a slash-separated list whose entries may carry a `*N` weight, like
+/// "mommy*4/daddy". Entries without one weigh 1
+fn parse_weighted_string(s: &str) -> (Vec<String>, Vec<u32>) {
+    parse_config_string(s)
+        .into_iter()
+        .map(|token| match token.rsplit_once('*') {
+            Some((value, weight)) if !value.trim().is_empty() => match weight.trim().parse() {
+                Ok(weight) => (value.trim().to_string(), weight),
+                Err(_) => (token, 1),
+            },
+            _ => (token, 1),
+        })
+        .unzip()
+}
+
+/// Read the mood override from a control file. A missing or empty file
+/// means no override
+fn read_mood_file(path: &str) -> Option<Vec<String>> {
+    let moods = parse_config_string(fs::read_to_string(path).ok()?.trim());
+    (!moods.is_empty()).then_some(moods)
+}
+
+/// Name of the user's shell (e.g. "fish") from a `SHELL` path like
+/// `/usr/bin/fish`
+pub fn shell_name(shell_path: &str) -> Option<String> {
+    let name = shell_path.rsplit(['/', '\\']).next()?.to_lowercase();
+    let name = name.strip_suffix(".exe").unwrap_or(&name);
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Parse a `CHANCE` value into a probability in `[0, 1]`. Values above 1 (or
+/// ending in `%`) are percentages, anything unparsable means always
+fn parse_chance(s: &str) -> f32 {
+    let s = s.trim();
+    let (value, percent) = match s.strip_suffix('%') {
+        Some(value) => (value.trim(), true),
+        None => (s, false),
+    };
+    match value.parse::<f32>() {
+        Ok(v) if v.is_nan() => 1.0,
+        Ok(v) if percent || v > 1.0 => (v / 100.0).clamp(0.0, 1.0),
+        Ok(v) => v.clamp(0.0, 1.0),
+        Err(_) => 1.0,
+    }
+}
+
+/// Split one style combo like "bold,italic" or "bold+italic" into its
+/// attributes
+fn parse_style_combo(combo: &str) -> Vec<String> {
+    combo
+        .split(|c: char| c == ',' || c == '+' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parse a `TIMEOUT` in seconds ("30", "0.5"). Zero, negative or malformed
+/// values mean no timeout
+fn parse_timeout(s: &str) -> Option<Duration> {
+    let secs: f64 = s.trim().parse().ok()?;
+    Duration::try_from_secs_f64(secs)
+        .ok()
+        .filter(|timeout| !timeout.is_zero())
+}
+
+/// Parse an exit code remapping like "101=1;2=0", skipping malformed entries
+fn parse_exit_map(s: &str) -> HashMap<i32, i32> {
+    s.split(';')
+        .filter_map(|entry| {
+            let (from, to) = entry.split_once('=')?;
+            Some((from.trim().parse().ok()?, to.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// Parse a list of exit codes like "130,143", skipping malformed entries
+fn parse_exit_codes(s: &str) -> Vec<i32> {
+    s.split(',')
+        .filter_map(|code| code.trim().parse().ok())
+        .collect()
+}
+
+/// Check if a `CI` env value means we're running under a CI system
+fn is_ci_value(value: &str) -> bool {
+    !value.is_empty() && value != "0" && !value.eq_ignore_ascii_case("false")
+}
+
+/// Check if the configured roles are all the gender-neutral "parent" role
+fn is_neutral_role(roles: &[String]) -> bool {
+    !roles.is_empty() && roles.iter().all(|r| r == "parent")
+}
+
+/// Read the settings from the environment and the default config file
+///
+/// ```
+/// let config = shell_mommy::load_config();
+/// assert!(!config.roles.is_empty());
+/// ```
 pub fn load_config() -> ConfigMommy {
+    load_config_with(&load_config_file().unwrap_or_default())
+}
+
+/// Build the config from the environment, falling back to `file` for
+/// anything the environment doesn't set, then to the built-in defaults
+pub fn load_config_with(file: &PartialConfig) -> ConfigMommy {
     // Detect binary info once
     let binary_info = BinaryInfo::detect();
     let env_prefix = get_env_prefix_from_binary(&binary_info);
+    let setting =
+        |suffix: &str| env_with_fallback(&env_prefix, suffix).or_else(|| file.get(suffix));
 
     // Load raw config values
-    let pronouns_raw =
-        env_with_fallback(&env_prefix, "PRONOUNS").unwrap_or_else(|| "her".to_string());
-    let roles_raw =
-        env_with_fallback(&env_prefix, "ROLES").unwrap_or_else(|| binary_info.role.clone());
-    let little_raw = env_with_fallback(&env_prefix, "LITTLE").unwrap_or_else(|| "girl".to_string());
-    let emotes_raw =
-        env_with_fallback(&env_prefix, "EMOTES").unwrap_or_else(|| "💖/💗/💓/💞".to_string());
-    let color_raw = env_with_fallback(&env_prefix, "COLOR").unwrap_or_else(|| "white".to_string());
-    let style_raw = env_with_fallback(&env_prefix, "STYLE").unwrap_or_else(|| "bold".to_string());
-    let color_rgb_raw = env_with_fallback(&env_prefix, "COLOR_RGB");
-    let moods_raw = env_with_fallback(&env_prefix, "MOODS").unwrap_or_else(|| "chill".to_string());
+    let roles_raw = setting("ROLES").unwrap_or_else(|| binary_info.role.clone());
+    let (roles, role_weights) = parse_weighted_string(&roles_raw);
+
+    // The gender-neutral "parent" role gets neutral defaults for anything the
+    // user didn't set explicitly
+    let (default_pronouns, default_little, default_emotes) = if is_neutral_role(&roles) {
+        ("their", "kiddo", "✨/🌟/💫/🌈")
+    } else {
+        ("her", "girl", "💖/💗/💓/💞")
+    };
+    let pronouns_raw = setting("PRONOUNS").unwrap_or_else(|| default_pronouns.to_string());
+    let little_raw = setting("LITTLE").unwrap_or_else(|| default_little.to_string());
+    let emotes_raw = setting("EMOTES").unwrap_or_else(|| default_emotes.to_string());
+    let color_env = setting("COLOR");
+    let style_raw = setting("STYLE").unwrap_or_else(|| "bold".to_string());
+    let color_rgb_raw = setting("COLOR_RGB");
+    // Mood colors only apply while the user hasn't picked colors themselves
+    let colors_explicit = color_env.is_some() || color_rgb_raw.is_some();
+    let color_raw = color_env.unwrap_or_else(|| "white".to_string());
+    let moods_raw = setting("MOODS");
 
     // Pre-parse all slash-separated config values
-    let pronouns = parse_config_string(&pronouns_raw);
-    let roles = parse_config_string(&roles_raw);
-    let little = parse_config_string(&little_raw);
-    let emotes = parse_config_string(&emotes_raw);
-    let moods = parse_config_string(&moods_raw);
+    let (pronouns, pronoun_weights) = parse_weighted_string(&pronouns_raw);
+    let (little, little_weights) = parse_weighted_string(&little_raw);
+    let (emotes, emote_weights) = parse_weighted_string(&emotes_raw);
+    // An empty EMOTES turns emotes off the same way NO_EMOTES does
+    let no_emotes = setting("NO_EMOTES").is_some_and(|v| v == "1") || emotes.is_empty();
+    // A mood control file, re-read on every run, takes precedence over MOODS
+    let mood_override = setting("MOOD_FILE").and_then(|path| read_mood_file(&path));
+    let moods_explicit = moods_raw.is_some() || mood_override.is_some();
+    let moods = mood_override
+        .unwrap_or_else(|| parse_config_string(moods_raw.as_deref().unwrap_or("chill")));
+    // Moods to try in order when the picked mood has nothing to say
+    let mood_fallback: Vec<String> = setting("MOOD_FALLBACK")
+        .map(|chain| {
+            chain
+                .split('>')
+                .map(|mood| mood.trim().to_lowercase())
+                .filter(|mood| !mood.is_empty())
+                .collect()
+        })
+        .unwrap_or_default();
     let colors = parse_config_string(&color_raw);
     let color_rgb = color_rgb_raw.map(|rgb| parse_config_string(&rgb));
+    let color_merge = setting("COLOR_MERGE").is_some_and(|v| v == "1");
+    let bg_color = setting("BG_COLOR")
+        .map(|c| parse_config_string(&c))
+        .unwrap_or_default();
+    let bg_color_rgb = setting("BG_COLOR_RGB").map(|rgb| parse_config_string(&rgb));
+    let gradient = setting("GRADIENT");
+    // Stderr by default so command output stays clean for pipes
+    let output = setting("OUTPUT")
+        .and_then(|v| v.trim().to_lowercase().parse().ok())
+        .unwrap_or_default();
+    // Operational messages get their own color, yellow unless set otherwise
+    let warn_color = setting("WARN_COLOR")
+        .and_then(|c| color_from_name(c.trim()).or_else(|| color_from_rgb_entry(&c)))
+        .unwrap_or(DynColors::Rgb(255, 255, 0));
 
-    // Pre-parse style combinations (each combo can have multiple comma-separated
-    // styles)
+    // Pre-parse style combinations (each combo can have multiple styles)
     let styles: Vec<Vec<String>> = parse_config_string(&style_raw)
-        .into_iter()
-        .map(|combo| {
-            combo
-                .split(',')
-                .map(|s| s.trim().to_string())
-                .filter(|s| !s.is_empty())
-                .collect()
-        })
+        .iter()
+        .map(|combo| parse_style_combo(combo))
         .collect();
+    let style_mix = setting("STYLE_MIX").is_some_and(|v| v == "1");
 
-    let aliases = env_with_fallback(&env_prefix, "ALIASES");
-    let affirmations = env_with_fallback(&env_prefix, "AFFIRMATIONS");
-    let needy = env_with_fallback(&env_prefix, "NEEDY").is_some_and(|v| v == "1");
-    let mood_mixing = env_with_fallback(&env_prefix, "MOOD_MIXING").is_some_and(|v| v == "1");
+    let aliases = setting("ALIASES");
+    let aliases_shell = setting("ALIASES_SHELL").is_some_and(|v| v == "1");
+    let affirmations = setting("AFFIRMATIONS");
+    let affirmations_merge = setting("AFFIRMATIONS_MERGE").is_some_and(|v| v == "1");
+    let pack = setting("PACK");
+    let chance = setting("CHANCE").map_or(1.0, |v| parse_chance(&v));
+    let max_affirmations_bytes = setting("MAX_AFFIRMATIONS_BYTES")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_AFFIRMATIONS_BYTES);
+    let recursion_max = setting("RECURSION_MAX")
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_RECURSION_MAX);
+    let recursion_quiet = setting("RECURSION_QUIET").is_some_and(|v| v == "1");
+    let needy = setting("NEEDY").is_some_and(|v| v == "1");
+    let mood_mixing = setting("MOOD_MIXING").is_some_and(|v| v == "1");
+    let mood_prefix = setting("MOOD_PREFIX").is_some_and(|v| v == "1");
+    let disable = setting("DISABLE").is_some_and(|v| v == "1");
+    let severity_match = setting("SEVERITY_MATCH").is_some_and(|v| v == "1");
+    let lock_identity = setting("LOCK_IDENTITY").is_some_and(|v| v == "1");
+    let transform_anywhere = setting("TRANSFORM_ANYWHERE").is_some_and(|v| v == "1");
+    let remember_mood = setting("REMEMBER_MOOD").is_some_and(|v| v == "1");
+    let mood_rotate = setting("MOOD_ROTATE").is_some_and(|v| v == "1");
+    let capture = setting("CAPTURE").is_some_and(|v| v == "1");
+    let timeout = setting("TIMEOUT").and_then(|v| parse_timeout(&v));
+    let no_repeat = setting("NO_REPEAT").is_some_and(|v| v == "1");
+    let scope = setting("SCOPE")
+        .and_then(|v| v.trim().to_lowercase().parse().ok())
+        .unwrap_or_default();
+    let exit_map = setting("EXIT_MAP")
+        .map(|v| parse_exit_map(&v))
+        .unwrap_or_default();
+    let neutral_codes = setting("NEUTRAL_CODES")
+        .map(|v| parse_exit_codes(&v))
+        .unwrap_or_default();
+    let personas = setting("PERSONA")
+        .map(|v| parse_config_string(&v))
+        .unwrap_or_default();
+    let persona_strict = setting("PERSONA_STRICT").is_some_and(|v| v == "1");
+    let normalize = setting("NO_NORMALIZE").is_none_or(|v| v != "1");
+    let showcase = setting("SHOWCASE").is_some_and(|v| v == "1");
+    let pager = setting("PAGER").is_some_and(|v| v == "1");
+    let usage = setting("USAGE");
+    let combo = setting("COMBO").is_some_and(|v| v == "1");
+    let shell = env::var("SHELL").ok().and_then(|s| shell_name(&s));
+    let no_env_propagate = setting("NO_ENV_PROPAGATE").is_some_and(|v| v == "1");
 
     // Special handling for ONLY_NEGATIVE (uses SHELL_MOMMY prefix, not
     // SHELL_MOMMYS)
     let only_negative = env::var("SHELL_MOMMY_ONLY_NEGATIVE").is_ok_and(|v| v == "1")
-        || env::var("CARGO_MOMMY_ONLY_NEGATIVE").is_ok_and(|v| v == "1");
+        || env::var("CARGO_MOMMY_ONLY_NEGATIVE").is_ok_and(|v| v == "1")
+        || file.get("ONLY_NEGATIVE").is_some_and(|v| v == "1");
+    // The two filters exclude each other, ONLY_NEGATIVE wins if both are set
+    let only_positive = !only_negative && setting("ONLY_POSITIVE").is_some_and(|v| v == "1");
 
-    let quiet = false; // Will be set later based on args
+    // `-q`/`--no-quiet` on the command line can still override this
+    let quiet = setting("QUIET").is_some_and(|v| v == "1");
+    let begging = false; // Will be set later based on args
+    let force_branch = None; // Same here
+    let force_color = None; // Same here
+    let json_output = false; // Same here
+    let interrupted = false; // Set once the command has run
+    let timed_out = false; // Same here
+    let last_stderr = None; // Same here
+    let (warn_count, error_count) = (0, 0); // Same here
 
-    // Get recursion limit from environment or default to 0
-    let recursion_limit = env::var("CARGO_MOMMY_RECURSION_LIMIT")
+    // Current nesting depth, passed down by the parent mommy (0 at the top)
+    let recursion_depth = env::var("CARGO_MOMMY_RECURSION_LIMIT")
         .or_else(|_| env::var("SHELL_MOMMY_RECURSION_LIMIT"))
         .ok()
         .and_then(|v| v.parse().ok())
         .unwrap_or(0);
 
+    // Plain CI output, either requested or auto-detected from the `CI` env var
+    // most CI systems set
+    let ci =
+        setting("CI").is_some_and(|v| v == "1") || env::var("CI").is_ok_and(|v| is_ci_value(&v));
+
+    // Shared seed so every layer of a recursion chain picks the same mood/style
+    let chain_seed = setting("CHAIN_CONSISTENT")
+        .is_some_and(|v| v == "1")
+        .then(|| {
+            env::var("CARGO_MOMMY_CHAIN_SEED")
+                .or_else(|_| env::var("SHELL_MOMMY_CHAIN_SEED"))
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or_else(|| fastrand::u64(..))
+        });
+
     ConfigMommy {
         pronouns,
         roles,
         little,
         emotes,
+        role_weights,
+        pronoun_weights,
+        little_weights,
+        emote_weights,
+        no_emotes,
         moods,
+        moods_explicit,
+        mood_fallback,
         colors,
+        colors_explicit,
         color_rgb,
+        color_merge,
+        bg_color,
+        bg_color_rgb,
+        gradient,
+        warn_color,
+        force_color,
+        json_output,
+        output,
         styles,
+        style_mix,
         aliases,
+        aliases_shell,
         affirmations,
+        affirmations_merge,
+        pack,
+        max_affirmations_bytes,
+        chance,
         needy,
         only_negative,
+        only_positive,
         quiet,
-        recursion_limit,
+        begging,
+        force_branch,
+        interrupted,
+        capture,
+        timeout,
+        timed_out,
+        last_stderr,
+        warn_count,
+        error_count,
+        recursion_depth,
+        recursion_max,
+        recursion_quiet,
         mood_mixing,
+        no_env_propagate,
+        mood_prefix,
+        chain_seed,
+        ci,
+        disable,
+        severity_match,
+        shell,
+        lock_identity,
+        transform_anywhere,
+        combo,
+        usage,
+        remember_mood,
+        mood_rotate,
+        no_repeat,
+        scope,
+        exit_map,
+        neutral_codes,
+        personas,
+        persona_strict,
+        normalize,
+        showcase,
+        pager,
         binary_info,
     }
 }
 
+// Mutex to serialize tests that modify environment variables, shared by
+// every module's tests
+#[cfg(test)]
+pub(crate) static ENV_TEST_LOCK: std::sync::LazyLock<std::sync::Mutex<()>> =
+    std::sync::LazyLock::new(|| std::sync::Mutex::new(()));
+
+// Point XDG_CONFIG_HOME at an empty dir, so a config.toml in the real config
+// dir stays out of the tests. Only with ENV_TEST_LOCK held
+#[cfg(test)]
+pub(crate) fn use_empty_config_dir() {
+    let empty = env::temp_dir().join(format!("mommy-test-no-config-{}", std::process::id()));
+    fs::create_dir_all(&empty).unwrap();
+    unsafe {
+        env::set_var("XDG_CONFIG_HOME", &empty);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::{
-        env,
-        sync::{LazyLock, Mutex},
-    };
-
-    // Mutex to serialize tests that modify environment variables
-    static ENV_TEST_LOCK: LazyLock<Mutex<()>> = LazyLock::new(|| Mutex::new(()));
 
     // Helper to clear all config‐related env vars.
     fn clear_all() {
@@ -204,6 +703,10 @@ mod tests {
             "SHELL_MOMMYS_MOOD_MIXING",
             "SHELL_MOMMY_ONLY_NEGATIVE",
             "SHELL_MOMMYS_MOODS",
+            "SHELL_MOMMYS_NO_ENV_PROPAGATE",
+            "SHELL_MOMMYS_MOOD_PREFIX",
+            "SHELL_MOMMYS_CHAIN_CONSISTENT",
+            "SHELL_MOMMY_CHAIN_SEED",
             "CARGO_MOMMYS_PRONOUNS",
             "CARGO_MOMMYS_ROLES",
             "CARGO_MOMMYS_LITTLE",
@@ -216,13 +719,115 @@ mod tests {
             "CARGO_MOMMYS_NEEDY",
             "CARGO_MOMMYS_MOOD_MIXING",
             "CARGO_MOMMYS_MOODS",
+            "CARGO_MOMMYS_NO_ENV_PROPAGATE",
+            "CARGO_MOMMYS_MOOD_PREFIX",
+            "CARGO_MOMMYS_CHAIN_CONSISTENT",
+            "CARGO_MOMMY_CHAIN_SEED",
             "CARGO_MOMMY_ONLY_NEGATIVE",
+            "SHELL_MOMMYS_CI",
+            "CARGO_MOMMYS_CI",
+            "SHELL_MOMMYS_DISABLE",
+            "CARGO_MOMMYS_DISABLE",
+            "SHELL_MOMMYS_SEVERITY_MATCH",
+            "CARGO_MOMMYS_SEVERITY_MATCH",
+            "SHELL_MOMMYS_MOOD_FILE",
+            "CARGO_MOMMYS_MOOD_FILE",
+            "SHELL_MOMMYS_MAX_AFFIRMATIONS_BYTES",
+            "CARGO_MOMMYS_MAX_AFFIRMATIONS_BYTES",
+            "SHELL_MOMMYS_LOCK_IDENTITY",
+            "CARGO_MOMMYS_LOCK_IDENTITY",
+            "SHELL_MOMMYS_COMBO",
+            "CARGO_MOMMYS_COMBO",
+            "SHELL_MOMMYS_USAGE",
+            "CARGO_MOMMYS_USAGE",
+            "SHELL_MOMMYS_REMEMBER_MOOD",
+            "CARGO_MOMMYS_REMEMBER_MOOD",
+            "SHELL_MOMMYS_EXIT_MAP",
+            "CARGO_MOMMYS_EXIT_MAP",
+            "SHELL_MOMMYS_PERSONA",
+            "CARGO_MOMMYS_PERSONA",
+            "SHELL_MOMMYS_PERSONA_STRICT",
+            "CARGO_MOMMYS_PERSONA_STRICT",
+            "SHELL_MOMMYS_NO_NORMALIZE",
+            "CARGO_MOMMYS_NO_NORMALIZE",
+            "SHELL_MOMMYS_SHOWCASE",
+            "CARGO_MOMMYS_SHOWCASE",
+            "SHELL_MOMMYS_PAGER",
+            "CARGO_MOMMYS_PAGER",
+            "SHELL_MOMMYS_PACK",
+            "CARGO_MOMMYS_PACK",
+            "SHELL_MOMMYS_COLOR_MERGE",
+            "CARGO_MOMMYS_COLOR_MERGE",
+            "SHELL_MOMMYS_WARN_COLOR",
+            "CARGO_MOMMYS_WARN_COLOR",
+            "SHELL_MOMMYS_SCOPE",
+            "CARGO_MOMMYS_SCOPE",
+            "SHELL_MOMMYS_TRANSFORM_ANYWHERE",
+            "CARGO_MOMMYS_TRANSFORM_ANYWHERE",
+            "SHELL_MOMMYS_MOOD_FALLBACK",
+            "CARGO_MOMMYS_MOOD_FALLBACK",
+            "SHELL_MOMMYS_NO_REPEAT",
+            "CARGO_MOMMYS_NO_REPEAT",
+            "SHELL_MOMMYS_AFFIRMATIONS_MERGE",
+            "CARGO_MOMMYS_AFFIRMATIONS_MERGE",
+            "SHELL_MOMMYS_BG_COLOR",
+            "CARGO_MOMMYS_BG_COLOR",
+            "SHELL_MOMMYS_BG_COLOR_RGB",
+            "CARGO_MOMMYS_BG_COLOR_RGB",
+            "SHELL_MOMMYS_CHANCE",
+            "CARGO_MOMMYS_CHANCE",
+            "SHELL_MOMMYS_MOOD_ROTATE",
+            "CARGO_MOMMYS_MOOD_ROTATE",
+            "SHELL_MOMMYS_ONLY_POSITIVE",
+            "CARGO_MOMMYS_ONLY_POSITIVE",
+            "SHELL_MOMMYS_CAPTURE",
+            "CARGO_MOMMYS_CAPTURE",
+            "SHELL_MOMMYS_RECURSION_MAX",
+            "CARGO_MOMMYS_RECURSION_MAX",
+            "SHELL_MOMMYS_NO_EMOTES",
+            "CARGO_MOMMYS_NO_EMOTES",
+            "SHELL_MOMMYS_GRADIENT",
+            "CARGO_MOMMYS_GRADIENT",
+            "SHELL_MOMMYS_OUTPUT",
+            "CARGO_MOMMYS_OUTPUT",
+            "SHELL_MOMMYS_QUIET",
+            "CARGO_MOMMYS_QUIET",
+            "SHELL_MOMMYS_RECURSION_QUIET",
+            "CARGO_MOMMYS_RECURSION_QUIET",
+            "SHELL_MOMMYS_NEUTRAL_CODES",
+            "CARGO_MOMMYS_NEUTRAL_CODES",
+            "SHELL_MOMMYS_ALIASES_SHELL",
+            "CARGO_MOMMYS_ALIASES_SHELL",
+            "SHELL_MOMMYS_TIMEOUT",
+            "CARGO_MOMMYS_TIMEOUT",
+            "SHELL_MOMMYS_STYLE_MIX",
+            "CARGO_MOMMYS_STYLE_MIX",
+            "CI",
         ];
         for k in &keys {
             unsafe {
                 env::remove_var(k);
             }
         }
+        use_empty_config_dir();
+    }
+
+    #[test]
+    fn test_binary_name_detection() {
+        // Expect: a trailing .exe in any case doesn't get in the way
+        assert!(is_cargo_subcommand("cargo-daddy.exe"));
+        assert_eq!(detect_role_from_binary("cargo-daddy.exe"), "daddy");
+        assert!(!is_cargo_subcommand("MOMMY.EXE"));
+        assert_eq!(detect_role_from_binary("MOMMY.EXE"), "mommy");
+        assert!(is_cargo_subcommand("Cargo-Daddy.Exe"));
+        assert_eq!(detect_role_from_binary("DADDY.EXE"), "daddy");
+
+        // Expect: plain unix names work as before
+        assert!(is_cargo_subcommand("cargo-mommy"));
+        assert_eq!(detect_role_from_binary("cargo-mommy"), "mommy");
+        assert!(!is_cargo_subcommand("daddy"));
+        assert_eq!(detect_role_from_binary("daddy"), "daddy");
+        assert!(!is_cargo_subcommand("mommy.exe.bak"));
     }
 
     #[test]
@@ -261,17 +866,62 @@ mod tests {
         assert!(config.roles == vec!["mommy"] || config.roles == vec!["daddy"]); // Depends on binary name
         assert_eq!(config.little, vec!["girl"]);
         assert_eq!(config.emotes, vec!["💖", "💗", "💓", "💞"]);
+        assert!(!config.no_emotes);
+        assert_eq!(config.gradient, None);
+        assert_eq!(config.output, Output::Stderr);
         assert_eq!(config.colors, vec!["white"]);
         assert_eq!(config.styles, vec![vec!["bold"]]);
         assert_eq!(config.color_rgb, None);
+        assert!(!config.colors_explicit);
         assert_eq!(config.aliases, None);
         assert_eq!(config.affirmations, None);
+        assert_eq!(
+            config.max_affirmations_bytes,
+            DEFAULT_MAX_AFFIRMATIONS_BYTES
+        );
         assert!(!config.needy);
         assert!(!config.only_negative);
+        assert!(!config.only_positive);
+        assert!(!config.capture);
         assert!(!config.mood_mixing);
+        assert!(!config.no_env_propagate);
+        assert!(!config.mood_prefix);
+        assert_eq!(config.chain_seed, None);
+        assert!(!config.ci);
+        assert!(!config.disable);
+        assert!(!config.severity_match);
+        assert!(!config.lock_identity);
+        assert!(!config.combo);
+        assert_eq!(config.usage, None);
+        assert!(!config.remember_mood);
+        assert!(config.exit_map.is_empty());
+        assert!(config.neutral_codes.is_empty());
+        assert!(config.personas.is_empty());
+        assert!(!config.persona_strict);
+        assert!(config.normalize);
+        assert!(!config.showcase);
+        assert!(!config.pager);
+        assert_eq!(config.pack, None);
+        assert!(!config.color_merge);
+        assert!(!config.style_mix);
+        assert_eq!(config.warn_color, DynColors::Rgb(255, 255, 0));
+        assert_eq!(config.scope, Scope::Global);
+        assert!(!config.transform_anywhere);
+        assert!(config.mood_fallback.is_empty());
+        assert!(!config.no_repeat);
+        assert!(!config.affirmations_merge);
+        assert!(config.bg_color.is_empty());
+        assert_eq!(config.bg_color_rgb, None);
+        assert_eq!(config.chance, 1.0);
+        assert!(!config.mood_rotate);
+        assert!(!config.moods_explicit);
         assert_eq!(config.moods, vec!["chill"]);
         assert!(!config.quiet);
-        assert_eq!(config.recursion_limit, 0);
+        assert!(!config.recursion_quiet);
+        assert!(!config.aliases_shell);
+        assert_eq!(config.timeout, None);
+        assert_eq!(config.recursion_depth, 0);
+        assert_eq!(config.recursion_max, DEFAULT_RECURSION_MAX);
     }
 
     #[test]
@@ -282,10 +932,13 @@ mod tests {
             env::set_var("SHELL_MOMMYS_PRONOUNS", "his");
             env::set_var("SHELL_MOMMYS_ROLES", "daddy");
             env::set_var("SHELL_MOMMYS_COLOR_RGB", "255,255,255");
+            env::set_var("SHELL_MOMMYS_COLOR", " Red/ /green//blue ");
             env::set_var("SHELL_MOMMYS_NEEDY", "1");
             env::set_var("SHELL_MOMMYS_MOOD_MIXING", "1");
             env::set_var("SHELL_MOMMY_ONLY_NEGATIVE", "1");
+            env::set_var("SHELL_MOMMYS_ONLY_POSITIVE", "1");
             env::set_var("SHELL_MOMMYS_MOODS", "ominous/thirsty");
+            env::set_var("SHELL_MOMMYS_NO_ENV_PROPAGATE", "1");
         }
         let config = load_config();
 
@@ -293,6 +946,8 @@ mod tests {
         assert_eq!(config.pronouns, vec!["his"]);
         assert_eq!(config.roles, vec!["daddy"]);
         assert_eq!(config.color_rgb, Some(vec!["255,255,255".to_string()]));
+        assert_eq!(config.colors, vec!["red", "green", "blue"]);
+        assert!(config.colors_explicit);
         assert!(config.needy, "expected 1, got {:#?}", config.needy);
         assert!(config.mood_mixing, "expected mood mixing to be enabled");
         assert!(
@@ -300,7 +955,11 @@ mod tests {
             "expected 1, got {:#?}",
             config.only_negative
         );
+        // Expect: ONLY_NEGATIVE wins over ONLY_POSITIVE
+        assert!(!config.only_positive);
         assert_eq!(config.moods, vec!["ominous", "thirsty"]);
+        assert!(config.moods_explicit);
+        assert!(config.no_env_propagate);
     }
 
     #[test]
@@ -332,4 +991,477 @@ mod tests {
         assert_eq!(parse_config_string(""), Vec::<String>::new());
         assert_eq!(parse_config_string(" / "), Vec::<String>::new());
     }
+
+    #[test]
+    fn test_parse_weighted_string() {
+        let (values, weights) = parse_weighted_string("Mommy*4/daddy/ auntie * 2 ");
+        assert_eq!(values, vec!["mommy", "daddy", "auntie"]);
+        assert_eq!(weights, vec![4, 1, 2]);
+
+        // Expect: plain lists weigh 1 each, and a '*' that isn't a weight stays
+        let (values, weights) = parse_weighted_string("a/b*/*3/c*x");
+        assert_eq!(values, vec!["a", "b*", "*3", "c*x"]);
+        assert_eq!(weights, vec![1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_parent_role_neutral_defaults() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        clear_all();
+        unsafe {
+            env::set_var("SHELL_MOMMYS_ROLES", "parent");
+        }
+        let config = load_config();
+
+        // Expect: neutral defaults for everything not set explicitly
+        assert_eq!(config.roles, vec!["parent"]);
+        assert_eq!(config.pronouns, vec!["their"]);
+        assert_eq!(config.little, vec!["kiddo"]);
+        assert_eq!(config.emotes, vec!["✨", "🌟", "💫", "🌈"]);
+    }
+
+    #[test]
+    fn test_parent_role_respects_overrides() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        clear_all();
+        unsafe {
+            env::set_var("SHELL_MOMMYS_ROLES", "parent");
+            env::set_var("SHELL_MOMMYS_PRONOUNS", "her");
+            env::set_var("SHELL_MOMMYS_LITTLE", "child");
+        }
+        let config = load_config();
+
+        // Expect: explicit values win, the rest stays neutral
+        assert_eq!(config.pronouns, vec!["her"]);
+        assert_eq!(config.little, vec!["child"]);
+        assert_eq!(config.emotes, vec!["✨", "🌟", "💫", "🌈"]);
+    }
+
+    #[test]
+    fn test_is_neutral_role() {
+        assert!(is_neutral_role(&["parent".to_string()]));
+        assert!(!is_neutral_role(&[
+            "parent".to_string(),
+            "mommy".to_string()
+        ]));
+        assert!(!is_neutral_role(&["daddy".to_string()]));
+        assert!(!is_neutral_role(&[]));
+    }
+
+    #[test]
+    fn test_chain_seed_honored_from_env() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        clear_all();
+        unsafe {
+            env::set_var("SHELL_MOMMY_CHAIN_SEED", "1234");
+        }
+
+        // Expect: the seed is ignored unless chain consistency is enabled
+        assert_eq!(load_config().chain_seed, None);
+
+        unsafe {
+            env::set_var("SHELL_MOMMYS_CHAIN_CONSISTENT", "1");
+        }
+        assert_eq!(load_config().chain_seed, Some(1234));
+    }
+
+    #[test]
+    fn test_chain_seed_generated_at_chain_start() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        clear_all();
+        unsafe {
+            env::set_var("SHELL_MOMMYS_CHAIN_CONSISTENT", "1");
+        }
+
+        // Expect: the outermost layer comes up with a seed of its own
+        assert!(load_config().chain_seed.is_some());
+    }
+
+    #[test]
+    fn test_ci_mode_explicit() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        clear_all();
+        unsafe {
+            env::set_var("SHELL_MOMMYS_CI", "1");
+        }
+        assert!(load_config().ci);
+    }
+
+    #[test]
+    fn test_ci_mode_auto_detected() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        clear_all();
+        unsafe {
+            env::set_var("CI", "true");
+        }
+        assert!(load_config().ci);
+
+        unsafe {
+            env::set_var("CI", "false");
+        }
+        assert!(!load_config().ci);
+    }
+
+    #[test]
+    fn test_is_ci_value() {
+        assert!(is_ci_value("true"));
+        assert!(is_ci_value("1"));
+        assert!(is_ci_value("yes"));
+        assert!(!is_ci_value(""));
+        assert!(!is_ci_value("0"));
+        assert!(!is_ci_value("FALSE"));
+    }
+
+    #[test]
+    fn test_mood_file_overrides_env() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        clear_all();
+        let path = env::temp_dir().join(format!("mommy-test-mood-{}", std::process::id()));
+        fs::write(&path, "ominous\n").unwrap();
+        unsafe {
+            env::set_var("SHELL_MOMMYS_MOODS", "chill");
+            env::set_var("SHELL_MOMMYS_MOOD_FILE", &path);
+        }
+        let config = load_config();
+        fs::remove_file(&path).unwrap();
+
+        // Expect: the control file wins over MOODS
+        assert_eq!(config.moods, vec!["ominous"]);
+    }
+
+    #[test]
+    fn test_mood_file_empty_falls_back() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        clear_all();
+        let path = env::temp_dir().join(format!("mommy-test-empty-mood-{}", std::process::id()));
+        fs::write(&path, "  \n").unwrap();
+        unsafe {
+            env::set_var("SHELL_MOMMYS_MOODS", "thirsty");
+            env::set_var("SHELL_MOMMYS_MOOD_FILE", &path);
+        }
+        let config = load_config();
+        fs::remove_file(&path).unwrap();
+
+        // Expect: an empty or missing control file changes nothing
+        assert_eq!(config.moods, vec!["thirsty"]);
+        assert_eq!(read_mood_file("/nonexistent/mood/file"), None);
+    }
+
+    #[test]
+    fn test_no_emotes() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        clear_all();
+        unsafe {
+            env::set_var("SHELL_MOMMYS_EMOTES", "");
+        }
+
+        // Expect: an empty EMOTES turns emotes off
+        assert!(load_config().no_emotes);
+
+        // Expect: so does NO_EMOTES, even with emotes set
+        unsafe {
+            env::set_var("SHELL_MOMMYS_EMOTES", "💖");
+            env::set_var("SHELL_MOMMYS_NO_EMOTES", "1");
+        }
+        let config = load_config();
+        clear_all();
+        assert!(config.no_emotes);
+    }
+
+    #[test]
+    fn test_recursion_max() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        clear_all();
+        unsafe {
+            env::set_var("SHELL_MOMMYS_RECURSION_MAX", " 5 ");
+            env::set_var("SHELL_MOMMY_RECURSION_LIMIT", "3");
+        }
+        let config = load_config();
+
+        // Expect: the max and the current depth are read separately
+        assert_eq!(config.recursion_max, 5);
+        assert_eq!(config.recursion_depth, 3);
+
+        // Expect: an invalid max falls back to the default
+        unsafe {
+            env::set_var("SHELL_MOMMYS_RECURSION_MAX", "lots");
+            env::remove_var("SHELL_MOMMY_RECURSION_LIMIT");
+        }
+        let config = load_config();
+        assert_eq!(config.recursion_max, DEFAULT_RECURSION_MAX);
+        assert_eq!(config.recursion_depth, 0);
+    }
+
+    #[test]
+    fn test_mood_fallback_chain() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        clear_all();
+        unsafe {
+            env::set_var("SHELL_MOMMYS_MOOD_FALLBACK", " Thirsty > ominous>>chill");
+        }
+        let config = load_config();
+
+        // Expect: the chain in order, trimmed and lowercased
+        assert_eq!(config.mood_fallback, vec!["thirsty", "ominous", "chill"]);
+    }
+
+    #[test]
+    fn test_shell_name() {
+        assert_eq!(shell_name("/usr/bin/fish").as_deref(), Some("fish"));
+        assert_eq!(shell_name("/bin/bash").as_deref(), Some("bash"));
+        assert_eq!(shell_name("zsh").as_deref(), Some("zsh"));
+        assert_eq!(
+            shell_name("C:\\Git\\bin\\BASH.EXE").as_deref(),
+            Some("bash")
+        );
+        assert_eq!(shell_name("/bin/"), None);
+        assert_eq!(shell_name(""), None);
+    }
+
+    #[test]
+    fn test_config_file_values() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        clear_all();
+        let file = parse_config_file(
+            Path::new("config.toml"),
+            r#"
+                moods = ["ominous", "thirsty"]
+                needy = true
+                color_rgb = "255,0,128"
+                chance = 50
+                max_affirmations_bytes = 1024
+                only_negative = true
+            "#,
+        )
+        .unwrap();
+
+        // Expect: every value from the file applies without any env vars
+        let config = load_config_with(&file);
+        assert_eq!(config.moods, vec!["ominous", "thirsty"]);
+        assert!(config.moods_explicit);
+        assert!(config.needy);
+        assert_eq!(config.color_rgb, Some(vec!["255,0,128".to_string()]));
+        assert_eq!(config.chance, 0.5);
+        assert_eq!(config.max_affirmations_bytes, 1024);
+        assert!(config.only_negative);
+
+        // Expect: JSON files work the same way
+        let file = parse_config_file(
+            Path::new("config.json"),
+            r#"{"roles": ["daddy"], "mood_mixing": false}"#,
+        )
+        .unwrap();
+        let config = load_config_with(&file);
+        assert_eq!(config.roles, vec!["daddy"]);
+        assert!(!config.mood_mixing);
+    }
+
+    #[test]
+    fn test_env_overrides_config_file() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        clear_all();
+        let file = parse_config_file(
+            Path::new("config.toml"),
+            "moods = \"ominous\"\npronouns = \"their\"",
+        )
+        .unwrap();
+        unsafe {
+            env::set_var("SHELL_MOMMYS_MOODS", "thirsty");
+        }
+
+        // Expect: the env var wins, the file still fills in the rest
+        let config = load_config_with(&file);
+        assert_eq!(config.moods, vec!["thirsty"]);
+        assert_eq!(config.pronouns, vec!["their"]);
+        clear_all();
+    }
+
+    #[test]
+    fn test_config_file_missing_or_invalid() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let dir = env::temp_dir().join(format!("mommy-test-config-{}", std::process::id()));
+        let config_dir = dir.join("mommy");
+        fs::create_dir_all(&config_dir).unwrap();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", &dir);
+        }
+
+        // Expect: no file means no file config
+        assert!(load_config_file().is_none());
+        assert!(read_config_file(&config_dir.join("config.toml")).is_err());
+
+        // Expect: a broken file is skipped instead of failing
+        fs::write(config_dir.join("config.toml"), "moods = [broken").unwrap();
+        assert!(load_config_file().is_none());
+        assert!(parse_config_file(Path::new("config.toml"), "moods = { nested = 1 }").is_err());
+
+        // Expect: a valid file is found in the config dir
+        fs::write(config_dir.join("config.toml"), "needy = true").unwrap();
+        assert_eq!(
+            load_config_file().and_then(|file| file.get("NEEDY")),
+            Some("1".to_string())
+        );
+
+        clear_all();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_default_affirmations_path() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let dir = env::temp_dir().join(format!("mommy-test-affirmations-{}", std::process::id()));
+        let config_dir = dir.join("mommy");
+        fs::create_dir_all(&config_dir).unwrap();
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", &dir);
+        }
+
+        // Expect: nothing to discover
+        assert_eq!(default_affirmations_path(), None);
+
+        // Expect: a broken file is passed over for one that loads
+        let json = config_dir.join("affirmations.json");
+        let toml = config_dir.join("affirmations.toml");
+        fs::write(&json, "{ broken").unwrap();
+        fs::write(
+            &toml,
+            "[moods.chill]\npositive = [\"good~\"]\nnegative = []\n",
+        )
+        .unwrap();
+        assert_eq!(default_affirmations_path(), Some(toml));
+
+        // Expect: JSON comes first when both load
+        fs::write(
+            &json,
+            r#"{"moods": {"chill": {"positive": [], "negative": []}}}"#,
+        )
+        .unwrap();
+        assert_eq!(default_affirmations_path(), Some(json));
+
+        clear_all();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_init_config_files() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let dir = env::temp_dir().join(format!("mommy-test-init-{}", std::process::id()));
+        let config_dir = dir.join("mommy");
+        let config = config_dir.join("config.toml");
+        let affirmations = config_dir.join("affirmations.json");
+        unsafe {
+            env::set_var("XDG_CONFIG_HOME", &dir);
+        }
+
+        // Expect: both files created, and both load
+        assert_eq!(
+            init_config_files(false).unwrap(),
+            vec![(config.clone(), true), (affirmations.clone(), true)]
+        );
+        assert!(read_config_file(&config).is_ok());
+        assert_eq!(default_affirmations_path(), Some(affirmations.clone()));
+
+        // Expect: existing files are kept without force
+        fs::write(&config, "needy = true").unwrap();
+        assert_eq!(
+            init_config_files(false).unwrap(),
+            vec![(config.clone(), false), (affirmations.clone(), false)]
+        );
+        assert_eq!(fs::read_to_string(&config).unwrap(), "needy = true");
+
+        // Expect: force overwrites them
+        assert_eq!(
+            init_config_files(true).unwrap(),
+            vec![(config.clone(), true), (affirmations.clone(), true)]
+        );
+        assert_eq!(fs::read_to_string(&config).unwrap(), STARTER_CONFIG);
+
+        // Expect: a config.json counts as an existing config
+        fs::remove_file(&config).unwrap();
+        let json = config_dir.join("config.json");
+        fs::write(&json, "{}").unwrap();
+        assert_eq!(init_config_files(false).unwrap()[0], (json, false));
+        assert!(!config.exists());
+
+        clear_all();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_parse_chance() {
+        // Expect: fractions and percentages
+        assert_eq!(parse_chance("0.25"), 0.25);
+        assert_eq!(parse_chance("25"), 0.25);
+        assert_eq!(parse_chance(" 25% "), 0.25);
+        assert_eq!(parse_chance("1"), 1.0);
+        assert_eq!(parse_chance("0"), 0.0);
+        assert_eq!(parse_chance("0.5%"), 0.005);
+
+        // Expect: out of range values clamp to [0, 1]
+        assert_eq!(parse_chance("150"), 1.0);
+        assert_eq!(parse_chance("-3"), 0.0);
+        assert_eq!(parse_chance("-0.5"), 0.0);
+
+        // Expect: invalid values mean full chance
+        assert_eq!(parse_chance("sometimes"), 1.0);
+        assert_eq!(parse_chance(""), 1.0);
+        assert_eq!(parse_chance("NaN"), 1.0);
+    }
+
+    #[test]
+    fn test_parse_exit_map() {
+        assert_eq!(
+            parse_exit_map("101=1;2=0"),
+            HashMap::from([(101, 1), (2, 0)])
+        );
+        assert_eq!(parse_exit_map(" 101 = 1 ; "), HashMap::from([(101, 1)]));
+        assert_eq!(parse_exit_map("x=1;3;4=y;5=6"), HashMap::from([(5, 6)]));
+        assert!(parse_exit_map("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_style_combo() {
+        // Expect: commas, pluses and spaces all separate attributes
+        assert_eq!(parse_style_combo("bold,italic"), vec!["bold", "italic"]);
+        assert_eq!(parse_style_combo("bold+italic"), vec!["bold", "italic"]);
+        assert_eq!(
+            parse_style_combo("bold+italic, underline"),
+            vec!["bold", "italic", "underline"]
+        );
+        assert!(parse_style_combo(" + ,").is_empty());
+
+        // Expect: the same escape codes either way
+        use owo_colors::OwoColorize;
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        clear_all();
+        let mut config = load_config();
+        config.colors = vec!["white".to_string()];
+        config.color_rgb = None;
+        config.bg_color.clear();
+        config.bg_color_rgb = None;
+        let mut styled = |combo: &str| {
+            config.styles = vec![parse_style_combo(combo)];
+            "Test"
+                .style(crate::color::random_style_pick(&config, None))
+                .to_string()
+        };
+        assert_eq!(styled("bold+italic"), styled("bold,italic"));
+        assert_ne!(styled("bold+italic"), styled("bold"));
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!(parse_timeout("30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_timeout(" 0.5 "), Some(Duration::from_millis(500)));
+        assert_eq!(parse_timeout("0"), None);
+        assert_eq!(parse_timeout("-1"), None);
+        assert_eq!(parse_timeout("soon"), None);
+    }
+
+    #[test]
+    fn test_parse_exit_codes() {
+        assert_eq!(parse_exit_codes("130,143"), vec![130, 143]);
+        assert_eq!(parse_exit_codes(" 130 , x,, 143 "), vec![130, 143]);
+        assert!(parse_exit_codes("").is_empty());
+    }
 }
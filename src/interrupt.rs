@@ -0,0 +1,112 @@
+//! Surviving Ctrl-C while the wrapped command runs, and passing it on.
+//!
+//! Mommy catches SIGINT so she can still say something once the child has
+//! stopped, and forwards it to the child: to its process group when it leads
+//! one of its own, else to the child itself, since a group shared with mommy
+//! also holds her parent's pipeline. A Ctrl-C from the terminal already
+//! reached a child in mommy's foreground group, so only signals from other
+//! processes (`kill -INT <mommy>`) are forwarded to it.
+
+use std::process::Child;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Where to forward SIGINT, as `kill` takes it: the child's pid, or its
+// process group negated. 0 while no child runs
+static FORWARD_TO: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn on_sigint(
+    _signum: libc::c_int,
+    info: *mut libc::siginfo_t,
+    _context: *mut libc::c_void,
+) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    let target = FORWARD_TO.load(Ordering::SeqCst);
+    // SAFETY: with SA_SIGINFO the kernel passes a valid siginfo_t
+    let from_terminal = unsafe { (*info).si_pid() } == 0;
+    if target == 0 || (target > 0 && from_terminal) {
+        return;
+    }
+    // SAFETY: kill is async-signal-safe
+    unsafe {
+        libc::kill(target, libc::SIGINT);
+    }
+}
+
+/// Catch SIGINT instead of dying from it
+pub fn catch() {
+    // SAFETY: the handler only touches atomics and calls kill, which are
+    // async-signal-safe, and the sigaction struct is fully initialized
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_sigint
+            as extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void)
+            as libc::sighandler_t;
+        // Restarting keeps reads of captured output going through a Ctrl-C
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+    }
+}
+
+/// Forward SIGINT to `child` (or its own process group) until `release`
+pub fn forward_to(child: &Child) {
+    let Ok(pid) = libc::pid_t::try_from(child.id()) else {
+        return;
+    };
+    // SAFETY: getpgid and getpgrp have no memory safety requirements
+    let (pgid, own_pgid) = unsafe { (libc::getpgid(pid), libc::getpgrp()) };
+    let target = if pgid > 0 && pgid != own_pgid {
+        -pgid
+    } else {
+        pid
+    };
+    FORWARD_TO.store(target, Ordering::SeqCst);
+}
+
+/// Stop forwarding SIGINT, once the child has been waited for
+pub fn release() {
+    FORWARD_TO.store(0, Ordering::SeqCst);
+}
+
+/// Whether SIGINT arrived since `catch` was called
+pub fn was_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ENV_TEST_LOCK;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::Command;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_forward_sigint() {
+        // Other tests' children would take over the forwarding target
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        catch();
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+        forward_to(&child);
+
+        // Expect: a `kill -INT` aimed at mommy stops the child too
+        let start = Instant::now();
+        let sent = Command::new("kill")
+            .args(["-INT", &std::process::id().to_string()])
+            .status()
+            .unwrap();
+        assert!(sent.success());
+        let status = child.wait().unwrap();
+        release();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(status.signal(), Some(libc::SIGINT));
+        assert!(was_interrupted());
+
+        // SAFETY: puts back the default action for the rest of the test run
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//! The affirmation engine behind the `mommy` binary, for embedding in other
+//! tools.
+//!
+//! ```no_run
+//! let config = shell_mommy::load_config();
+//! let status = std::process::Command::new("true").status().unwrap();
+//! eprintln!("{}", shell_mommy::affirm(status.code().unwrap_or(1), &config));
+//! ```
+
+mod affirmations;
+mod color;
+mod config;
+#[cfg(unix)]
+mod interrupt;
+mod localtime;
+mod mommy;
+mod random;
+mod state;
+mod utils;
+mod validate;
+
+pub use affirmations::{Affirmation, AffirmationData, Affirmations};
+pub use color::random_style_pick;
+pub use config::{load_config, ConfigMommy};
+pub use mommy::{affirm, affirm_with_rng, mommy};
+pub use utils::fill_template;
@@ -0,0 +1,427 @@
+//! The local hour without a time zone library or a subprocess: `TZ` is read
+//! as a POSIX rule or a zoneinfo name, otherwise `/etc/localtime`. Anything
+//! that can't be worked out counts as UTC
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECS_PER_DAY: i64 = 86_400;
+
+/// Where zone names like `Europe/Berlin` in `TZ` are looked up
+const ZONEINFO_DIR: &str = "/usr/share/zoneinfo";
+
+/// Current local hour (0-23)
+pub fn local_hour() -> u8 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(0));
+    hour_of(now + utc_offset(now))
+}
+
+/// Hour of the day for seconds since the epoch
+fn hour_of(secs: i64) -> u8 {
+    u8::try_from(secs.rem_euclid(SECS_PER_DAY) / 3600).unwrap_or(0)
+}
+
+/// Seconds east of UTC at `now`, from `TZ` or `/etc/localtime`
+fn utc_offset(now: i64) -> i64 {
+    match std::env::var("TZ") {
+        Ok(tz) if !tz.is_empty() => tz_offset(&tz, now),
+        _ => tzif_file_offset(Path::new("/etc/localtime"), now),
+    }
+    .unwrap_or(0)
+}
+
+/// Offset for a `TZ` value: a POSIX rule like `CET-1CEST,M3.5.0,M10.5.0/3`,
+/// or a zoneinfo name or path (optionally after a `:`)
+fn tz_offset(tz: &str, now: i64) -> Option<i64> {
+    let name = match tz.strip_prefix(':') {
+        Some(name) => name,
+        None => {
+            if let Some(rule) = PosixTz::parse(tz) {
+                return Some(rule.offset_at(now));
+            }
+            tz
+        }
+    };
+    let path = Path::new(name);
+    if path.is_absolute() {
+        tzif_file_offset(path, now)
+    } else {
+        tzif_file_offset(&Path::new(ZONEINFO_DIR).join(path), now)
+    }
+}
+
+fn tzif_file_offset(path: &Path, now: i64) -> Option<i64> {
+    tzif_offset(&std::fs::read(path).ok()?, now)
+}
+
+/// Offset at `now` from the contents of a TZif (zoneinfo) file
+fn tzif_offset(data: &[u8], now: i64) -> Option<i64> {
+    if !data.starts_with(b"TZif") {
+        return None;
+    }
+    let counts = |header: usize| -> Option<[usize; 6]> {
+        let mut counts = [0; 6];
+        for (i, count) in counts.iter_mut().enumerate() {
+            let at = header + 20 + i * 4;
+            let bytes = data.get(at..at + 4)?;
+            *count = u32::from_be_bytes(bytes.try_into().ok()?) as usize;
+        }
+        Some(counts)
+    };
+    let block_len = |counts: [usize; 6], time_size: usize| {
+        let [isutcnt, isstdcnt, leapcnt, timecnt, typecnt, charcnt] = counts;
+        timecnt * (time_size + 1)
+            + typecnt * 6
+            + charcnt
+            + leapcnt * (time_size + 4)
+            + isstdcnt
+            + isutcnt
+    };
+
+    // Version 2 and later repeat the data with 64-bit times, followed by a
+    // POSIX rule for times after the last transition
+    let mut header = 0;
+    let mut time_size = 4;
+    let mut footer = None;
+    if data.get(4).is_some_and(|version| *version >= b'2') {
+        header = 44 + block_len(counts(0)?, 4);
+        time_size = 8;
+        let end = header + 44 + block_len(counts(header)?, 8);
+        footer = data
+            .get(end..)
+            .and_then(|rest| std::str::from_utf8(rest).ok())
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty());
+    }
+    let [_, _, _, timecnt, typecnt, _] = counts(header)?;
+    let times_at = header + 44;
+    let indices_at = times_at + timecnt * time_size;
+    let types_at = indices_at + timecnt;
+
+    let transition = |i: usize| -> Option<i64> {
+        let bytes = data.get(times_at + i * time_size..times_at + (i + 1) * time_size)?;
+        Some(if time_size == 8 {
+            i64::from_be_bytes(bytes.try_into().ok()?)
+        } else {
+            i64::from(i32::from_be_bytes(bytes.try_into().ok()?))
+        })
+    };
+    let utoff = |kind: usize| -> Option<i64> {
+        if kind >= typecnt {
+            return None;
+        }
+        let bytes = data.get(types_at + kind * 6..types_at + kind * 6 + 4)?;
+        Some(i64::from(i32::from_be_bytes(bytes.try_into().ok()?)))
+    };
+
+    let mut last = None;
+    for i in 0..timecnt {
+        if transition(i)? > now {
+            break;
+        }
+        last = Some(i);
+    }
+    let past_transitions = timecnt == 0 || last == Some(timecnt - 1);
+    if past_transitions {
+        if let Some(rule) = footer.and_then(PosixTz::parse) {
+            return Some(rule.offset_at(now));
+        }
+    }
+    match last {
+        Some(i) => utoff(usize::from(*data.get(indices_at + i)?)),
+        None => utoff(0),
+    }
+}
+
+/// A POSIX `TZ` rule. Offsets are seconds east of UTC
+#[derive(Debug, PartialEq)]
+struct PosixTz {
+    std_offset: i64,
+    dst: Option<Dst>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Dst {
+    offset: i64,
+    start: DstRule,
+    end: DstRule,
+}
+
+/// `Mm.w.d/time`: day `d` (0 is Sunday) of week `w` (5 is the last) of month
+/// `m`, at `time` seconds past local midnight
+#[derive(Debug, PartialEq)]
+struct DstRule {
+    month: u32,
+    week: u32,
+    weekday: i64,
+    time: i64,
+}
+
+impl PosixTz {
+    fn parse(s: &str) -> Option<Self> {
+        let rest = take_name(s)?;
+        let (std_west, rest) = take_time(rest)?;
+        let std_offset = -std_west;
+        if rest.is_empty() {
+            return Some(Self {
+                std_offset,
+                dst: None,
+            });
+        }
+
+        let rest = take_name(rest)?;
+        let (offset, rest) = match take_time(rest) {
+            Some((dst_west, rest)) => (-dst_west, rest),
+            None => (std_offset + 3600, rest),
+        };
+        // Without rules, the US ones like most systems assume
+        let (start, end) = if rest.is_empty() {
+            (DstRule::parse("M3.2.0")?, DstRule::parse("M11.1.0")?)
+        } else {
+            let (start, end) = rest.strip_prefix(',')?.split_once(',')?;
+            (DstRule::parse(start)?, DstRule::parse(end)?)
+        };
+        Some(Self {
+            std_offset,
+            dst: Some(Dst { offset, start, end }),
+        })
+    }
+
+    fn offset_at(&self, now: i64) -> i64 {
+        let Some(dst) = &self.dst else {
+            return self.std_offset;
+        };
+        let (year, _, _) = civil_from_days((now + self.std_offset).div_euclid(SECS_PER_DAY));
+        // Start is given in standard time and end in daylight time
+        let start = dst.start.local_secs(year) - self.std_offset;
+        let end = dst.end.local_secs(year) - dst.offset;
+        let in_dst = if start < end {
+            (start..end).contains(&now)
+        } else {
+            !(end..start).contains(&now)
+        };
+        if in_dst {
+            dst.offset
+        } else {
+            self.std_offset
+        }
+    }
+}
+
+impl DstRule {
+    fn parse(s: &str) -> Option<Self> {
+        let (date, time) = match s.split_once('/') {
+            Some((date, time)) => {
+                let (time, rest) = take_time(time)?;
+                if !rest.is_empty() {
+                    return None;
+                }
+                (date, time)
+            }
+            None => (s, 2 * 3600),
+        };
+        let mut parts = date.strip_prefix('M')?.split('.');
+        let month = parts
+            .next()?
+            .parse()
+            .ok()
+            .filter(|m| (1..=12).contains(m))?;
+        let week = parts.next()?.parse().ok().filter(|w| (1..=5).contains(w))?;
+        let weekday = parts.next()?.parse().ok().filter(|d| (0..=6).contains(d))?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            month,
+            week,
+            weekday,
+            time,
+        })
+    }
+
+    /// Seconds since the epoch of the change in `year`, in local wall time
+    fn local_secs(&self, year: i64) -> i64 {
+        let first = days_from_civil(year, self.month, 1);
+        let first_weekday = (first + 4).rem_euclid(7);
+        let mut day = (self.weekday - first_weekday).rem_euclid(7) + i64::from(self.week - 1) * 7;
+        let next_month = if self.month == 12 {
+            days_from_civil(year + 1, 1, 1)
+        } else {
+            days_from_civil(year, self.month + 1, 1)
+        };
+        // Week 5 means the last such day, which may be in week 4
+        while first + day >= next_month {
+            day -= 7;
+        }
+        (first + day) * SECS_PER_DAY + self.time
+    }
+}
+
+/// Skip a zone abbreviation, `EST` or `<+03>`
+fn take_name(s: &str) -> Option<&str> {
+    if let Some(quoted) = s.strip_prefix('<') {
+        return quoted.split_once('>').map(|(_, rest)| rest);
+    }
+    let len = s
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(s.len());
+    (len >= 3).then(|| &s[len..])
+}
+
+/// Take a `[+-]hh[:mm[:ss]]` time in seconds
+fn take_time(s: &str) -> Option<(i64, &str)> {
+    let (sign, s) = match s.as_bytes().first() {
+        Some(b'-') => (-1, &s[1..]),
+        Some(b'+') => (1, &s[1..]),
+        _ => (1, s),
+    };
+    let len = s
+        .find(|c: char| !c.is_ascii_digit() && c != ':')
+        .unwrap_or(s.len());
+    if len == 0 {
+        return None;
+    }
+    let mut secs = 0;
+    for (part, scale) in s[..len].split(':').zip([3600, 60, 1]) {
+        secs += part.parse::<i64>().ok()? * scale;
+    }
+    Some((sign * secs, &s[len..]))
+}
+
+/// Days since 1970-01-01 for a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Year, month and day for days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = u32::try_from(day_of_year - (153 * mp + 2) / 5 + 1).unwrap_or(1);
+    let month = u32::try_from(if mp < 10 { mp + 3 } else { mp - 9 }).unwrap_or(1);
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Seconds since the epoch for a UTC date and hour
+    fn utc(year: i64, month: u32, day: u32, hour: i64) -> i64 {
+        days_from_civil(year, month, day) * SECS_PER_DAY + hour * 3600
+    }
+
+    #[test]
+    fn test_civil_days_round_trip() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2000, 3, 1), 11_017);
+        for days in [-800_000, -1, 0, 59, 11_016, 19_782, 2_000_000] {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn test_posix_tz_fixed() {
+        let offset = |tz: &str| PosixTz::parse(tz).map(|tz| tz.offset_at(0));
+
+        // Expect: POSIX offsets count west, so they flip sign
+        assert_eq!(offset("UTC0"), Some(0));
+        assert_eq!(offset("EST5"), Some(-5 * 3600));
+        assert_eq!(offset("<+0330>-3:30"), Some(3 * 3600 + 1800));
+
+        // Expect: names alone (zoneinfo names) aren't rules
+        assert_eq!(offset("UTC"), None);
+        assert_eq!(offset("Europe/Berlin"), None);
+    }
+
+    #[test]
+    fn test_posix_tz_dst() {
+        let us = PosixTz::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        assert_eq!(us.offset_at(utc(2024, 1, 15, 12)), -5 * 3600);
+        assert_eq!(us.offset_at(utc(2024, 7, 1, 12)), -4 * 3600);
+        // Expect: the switch at 2:00 local on the second Sunday of March
+        assert_eq!(us.offset_at(utc(2024, 3, 10, 6)), -5 * 3600);
+        assert_eq!(us.offset_at(utc(2024, 3, 10, 7)), -4 * 3600);
+
+        // Expect: "last Sunday" rules and a custom change time
+        let eu = PosixTz::parse("CET-1CEST,M3.5.0,M10.5.0/3").unwrap();
+        assert_eq!(eu.offset_at(utc(2024, 1, 15, 12)), 3600);
+        assert_eq!(eu.offset_at(utc(2024, 7, 1, 12)), 2 * 3600);
+        assert_eq!(eu.offset_at(utc(2024, 10, 27, 0)), 2 * 3600);
+        assert_eq!(eu.offset_at(utc(2024, 10, 27, 1)), 3600);
+
+        // Expect: southern summers wrap around the new year
+        let au = PosixTz::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(au.offset_at(utc(2024, 1, 15, 12)), 11 * 3600);
+        assert_eq!(au.offset_at(utc(2024, 7, 1, 12)), 10 * 3600);
+    }
+
+    /// A version 2 TZif file with one transition to `utoff` at `at`, and
+    /// `footer` as the rule after it
+    fn tzif(at: i64, utoff: i32, footer: &str) -> Vec<u8> {
+        let header = |time_size: usize| {
+            let mut header = b"TZif2".to_vec();
+            header.extend([0; 15]);
+            for count in [0u32, 0, 0, 1, 2, 4] {
+                header.extend(count.to_be_bytes());
+            }
+            let mut block = match time_size {
+                8 => at.to_be_bytes().to_vec(),
+                _ => (at as i32).to_be_bytes().to_vec(),
+            };
+            block.push(1);
+            block.extend(0i32.to_be_bytes());
+            block.extend([0, 0]);
+            block.extend(utoff.to_be_bytes());
+            block.extend([0, 0]);
+            block.extend(b"UTC\0");
+            header.extend(block);
+            header
+        };
+        let mut data = header(4);
+        data.extend(header(8));
+        data.extend(format!("\n{footer}\n").bytes());
+        data
+    }
+
+    #[test]
+    fn test_tzif_offset() {
+        let data = tzif(1000, 3600, "");
+
+        // Expect: type 0 before the first transition, its type after
+        assert_eq!(tzif_offset(&data, 999), Some(0));
+        assert_eq!(tzif_offset(&data, 1000), Some(3600));
+
+        // Expect: the footer rule takes over after the last transition
+        let data = tzif(1000, 3600, "EST5EDT,M3.2.0,M11.1.0");
+        assert_eq!(tzif_offset(&data, 999), Some(0));
+        assert_eq!(tzif_offset(&data, utc(2024, 7, 1, 12)), Some(-4 * 3600));
+
+        // Expect: anything else isn't a zoneinfo file
+        assert_eq!(tzif_offset(b"not a zone", 0), None);
+        assert_eq!(tzif_offset(&data[..50], 0), None);
+    }
+
+    #[test]
+    fn test_hour_of() {
+        assert_eq!(hour_of(0), 0);
+        assert_eq!(hour_of(utc(2024, 7, 1, 13) + 59 * 60), 13);
+        assert_eq!(hour_of(-3600), 23);
+        assert!(local_hour() < 24);
+    }
+}
@@ -1,15 +1,7 @@
-mod affirmations;
-mod color;
-mod config;
-mod mommy;
-#[cfg(feature = "beg")]
-mod state;
-mod utils;
-
 fn main() {
     // Changed the logic here to pass the exit code of the program
     // as exit code of mommy itself, so the exit code preserved for debug purposes:
-    match mommy::mommy() {
+    match shell_mommy::mommy() {
         Ok(code) => std::process::exit(code),
         Err(e) => {
             eprintln!("Error: {e:?}");
@@ -1,34 +1,522 @@
 use crate::{
     affirmations::{
-        load_affirmations_with_mood_mixing, load_custom_affirmations_with_mood_mixing,
-        AffirmationData,
+        available_moods, check_affirmations, combo_template, load_affirmations_with_mood_mixing,
+        load_custom_affirmations_multi, load_custom_affirmations_with_mood_mixing,
+        load_merged_affirmations_with_mood_mixing, load_pack_affirmations_with_mood_mixing,
+        mood_glyph, prefer_persona, prefer_severity, prefer_shell, prefer_unseen,
+        split_affirmation_paths, Affirmation, AffirmationData, Branch, Severity,
+        EMBEDDED_AFFIRMATIONS_JSON,
+    },
+    color::{
+        colorize_output, output_style, paint_emote_gradient, paint_gradient, parse_gradient,
+        unknown_colors, ColorMode,
+    },
+    config::{
+        default_affirmations_path, get_env_prefix_from_binary, init_config_files, load_config,
+        load_config_with, read_config_file, shell_name, ConfigMommy,
+    },
+    random,
+    state::{FileShowcaseStore, FileTextStore, Showcase, ShowcaseStore, TextStore},
+    utils::{
+        fill_template, fill_template_with_code, graceful_print, normalize_whitespace, print_paged,
+        random_vec_pick, shell_quote, unknown_placeholders, warn,
     },
-    color::random_style_pick,
-    config::{load_config, ConfigMommy},
-    utils::{fill_template, graceful_print, random_vec_pick, shell_quote},
 };
-use owo_colors::OwoColorize;
+use owo_colors::{OwoColorize, Style};
+use serde::Serialize;
 use std::{
-    env,
-    process::{exit, Command},
+    collections::HashMap,
+    env, fs,
+    hint::black_box,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::Path,
+    process::{exit, Child, Command, ExitStatus, Stdio},
+    thread::JoinHandle,
+    time::{Duration, Instant},
 };
 
-const RECURSION_LIMIT: usize = 100;
-
 #[inline]
-fn choose_template<'a>(json_template: Option<&'a [String]>, default_template: &'a str) -> &'a str {
-    match json_template {
-        Some(templates) if !templates.is_empty() => {
-            let idx = fastrand::usize(..templates.len());
-            templates[idx].as_str()
+fn choose_template<'a>(candidates: &[&'a Affirmation], default_template: &'a str) -> &'a str {
+    if candidates.is_empty() {
+        default_template
+    } else {
+        choose_weighted(candidates)
+    }
+}
+
+/// Like `choose_template`, but never picks `last` again while the candidates
+/// have anything else to offer
+fn choose_template_no_repeat<'a>(
+    candidates: &[&'a Affirmation],
+    default_template: &'a str,
+    last: Option<&str>,
+) -> &'a str {
+    let mut candidates = candidates.to_vec();
+    if let Some(last) = last {
+        prefer_unseen(&mut candidates, &[last.to_string()]);
+    }
+    choose_template(&candidates, default_template)
+}
+
+/// Pick a line with a chance proportional to its weight (0 counts as 1).
+/// `candidates` must not be empty
+fn choose_weighted<'a>(candidates: &[&'a Affirmation]) -> &'a str {
+    let weight = |a: &Affirmation| u64::from(a.weight.max(1));
+    let total: u64 = candidates.iter().map(|a| weight(a)).sum();
+    let mut roll = random::u64(..total);
+    candidates
+        .iter()
+        .find(|a| {
+            let w = weight(a);
+            if roll < w {
+                return true;
+            }
+            roll -= w;
+            false
+        })
+        .map_or("", |a| a.text.as_str())
+}
+
+/// Template for exit code 127, which the shell uses for "command not found"
+const COMMAND_NOT_FOUND: &str = "{roles} couldn't find that command, {little}~ {emotes}";
+
+/// Template for exit code 137, a child killed by SIGKILL (usually the OOM
+/// killer on Linux)
+const OUT_OF_MEMORY: &str = "that was too much to carry, let's use less memory, {little}~ {emotes}";
+
+/// Iterations for each timed step of the hidden `bench` subcommand
+const BENCH_ITERATIONS: u32 = 1000;
+
+/// Template for a command the user stopped with Ctrl-C
+const INTERRUPTED: &str = "taking a break is okay, {little}~ {emotes}";
+
+/// Template for a command killed for running past `TIMEOUT`
+const TIMED_OUT: &str =
+    "that was taking forever, so {roles} stopped it for you, {little}~ {emotes}";
+
+/// Exit code for a command killed for running past `TIMEOUT`, the same one
+/// GNU timeout uses
+const TIMEOUT_EXIT_CODE: i32 = 124;
+
+/// How often to check on a command running with a `TIMEOUT`
+const TIMEOUT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long to wait for captured output after killing a command for its
+/// `TIMEOUT`. Its own children may keep the pipes open long after it's gone
+const TIMEOUT_OUTPUT_GRACE: Duration = Duration::from_millis(200);
+
+/// Template for a captured failure whose last stderr line mentions an error
+const STDERR_ERROR: &str =
+    "that error message looks scary, let's read it together, {little}~ {emotes}";
+
+/// Built-in templates for exit codes that mean something more specific than
+/// a generic failure
+fn exit_code_template(exit_code: i32, is_cargo: bool) -> Option<&'static str> {
+    match exit_code {
+        127 if !is_cargo => Some(COMMAND_NOT_FOUND),
+        137 if cfg!(target_os = "linux") => Some(OUT_OF_MEMORY),
+        _ => None,
+    }
+}
+
+/// Exit code to report for a finished child. On unix a child killed by a
+/// signal reports 128 + signal like the shell does (137 for the SIGKILL the
+/// OOM killer uses), so it still lands on the negative branch
+fn exit_code_from_status(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/// How the wrapped command finished
+#[derive(Debug, Default)]
+struct Finished {
+    exit_code: i32,
+    // Last non-empty stderr line, only with CAPTURE
+    last_stderr: Option<String>,
+    // Warnings and errors reported on stderr, only with CAPTURE
+    counts: DiagnosticCounts,
+    timed_out: bool,
+}
+
+/// Run `cmd` to completion. With `capture` its stdout and stderr are piped
+/// and streamed through as they arrive, and the last non-empty stderr line and
+/// the warnings and errors on stderr are handed back alongside the exit code. With a `timeout` the command is
+/// killed once it runs longer and reports `TIMEOUT_EXIT_CODE`
+fn run_status(cmd: &mut Command, capture: bool, timeout: Option<Duration>) -> io::Result<Finished> {
+    if capture {
+        cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+    let mut child = cmd.spawn()?;
+    #[cfg(unix)]
+    crate::interrupt::forward_to(&child);
+    let status = wait_captured(&mut child, timeout);
+    #[cfg(unix)]
+    crate::interrupt::release();
+    status
+}
+
+/// Wait for a spawned `child`, see `run_status`
+fn wait_captured(child: &mut Child, timeout: Option<Duration>) -> io::Result<Finished> {
+    let forward = child.stdout.take().map(|mut stdout| {
+        std::thread::spawn(move || {
+            let mut buf = [0; 8192];
+            let mut out = io::stdout();
+            while let Ok(n @ 1..) = stdout.read(&mut buf) {
+                if out.write_all(&buf[..n]).and_then(|()| out.flush()).is_err() {
+                    break;
+                }
+            }
+        })
+    });
+    let last_line = child.stderr.take().map(|stderr| {
+        std::thread::spawn(move || {
+            let mut last_line = None;
+            let mut counts = DiagnosticCounts::default();
+            let mut reader = BufReader::new(stderr);
+            let mut line = Vec::new();
+            while let Ok(1..) = reader.read_until(b'\n', &mut line) {
+                let _ = io::stderr().write_all(&line);
+                let text = String::from_utf8_lossy(&line).trim().to_string();
+                counts.add_line(&text);
+                if !text.is_empty() {
+                    last_line = Some(text);
+                }
+                line.clear();
+            }
+            (last_line, counts)
+        })
+    });
+
+    let status = wait_with_timeout(child, timeout)?;
+    // After a timeout, readers still blocked past the grace period are left
+    // behind rather than waited on
+    let deadline = status
+        .is_none()
+        .then(|| Instant::now() + TIMEOUT_OUTPUT_GRACE);
+    let (last_stderr, counts) = last_line
+        .and_then(|reader| join_until(reader, deadline))
+        .unwrap_or_default();
+    if let Some(forward) = forward {
+        join_until(forward, deadline);
+    }
+    Ok(Finished {
+        exit_code: status.map_or(TIMEOUT_EXIT_CODE, exit_code_from_status),
+        last_stderr,
+        counts,
+        timed_out: status.is_none(),
+    })
+}
+
+/// Wait for `child`, killing it once it runs past `timeout`. `None` means it
+/// was killed
+fn wait_with_timeout(
+    child: &mut Child,
+    timeout: Option<Duration>,
+) -> io::Result<Option<ExitStatus>> {
+    let Some(timeout) = timeout else {
+        return child.wait().map(Some);
+    };
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if start.elapsed() >= timeout {
+            // It may have exited in the meantime, which is fine too
+            let _ = child.kill();
+            child.wait()?;
+            return Ok(None);
+        }
+        std::thread::sleep(TIMEOUT_POLL_INTERVAL);
+    }
+}
+
+/// Join `thread`, giving up once `deadline` passes (never without one)
+fn join_until<T>(thread: JoinHandle<T>, deadline: Option<Instant>) -> Option<T> {
+    if let Some(deadline) = deadline {
+        while !thread.is_finished() {
+            if Instant::now() >= deadline {
+                return None;
+            }
+            std::thread::sleep(TIMEOUT_POLL_INTERVAL);
+        }
+    }
+    thread.join().ok()
+}
+
+/// Whether a captured stderr line reports an error
+fn mentions_error(line: &str) -> bool {
+    line.to_lowercase().contains("error")
+}
+
+/// Warnings and errors counted in captured output, for `{warn_count}` and
+/// `{error_count}`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct DiagnosticCounts {
+    warnings: usize,
+    errors: usize,
+}
+
+impl DiagnosticCounts {
+    /// Count a line if it starts a warning or an error the way cargo and
+    /// rustc print them (`warning: ...`, `error[E0308]: ...`), colored or
+    /// not. Summaries like "generated 3 warnings" or "could not compile"
+    /// aren't counted again
+    fn add_line(&mut self, line: &str) {
+        let line = strip_ansi(line);
+        let line = line.trim_start();
+        let is = |kind: &str| {
+            line.strip_prefix(kind)
+                .is_some_and(|rest| rest.starts_with(':') || rest.starts_with('['))
+        };
+        if is("warning") {
+            if !line.contains(" generated ") && !line.contains("build failed") {
+                self.warnings += 1;
+            }
+        } else if is("error")
+            && !line.contains("could not compile")
+            && !line.contains("aborting due to")
+        {
+            self.errors += 1;
+        }
+    }
+}
+
+/// `line` without ANSI escape sequences (`ESC [ ... letter`)
+fn strip_ansi(line: &str) -> String {
+    let mut plain = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            if chars.next() == Some('[') {
+                // Parameters run up to the final byte, a letter or one of @[\]^_`{|}~
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+/// Plain, deterministic outcome line for CI logs
+fn ci_line(role: &str, exit_code: i32) -> String {
+    let outcome = if exit_code == 0 { "success" } else { "failure" };
+    format!("{role}: {outcome} (exit {exit_code})")
+}
+
+/// Usage message, from the configured template or the default for the
+/// current mode. `{bin}` is replaced with how mommy was invoked
+fn usage_line(config: &ConfigMommy, program: &str) -> String {
+    let bin = if config.binary_info.is_cargo_subcommand {
+        format!("cargo {}", config.binary_info.role)
+    } else {
+        program.to_string()
+    };
+    let template = config
+        .usage
+        .as_deref()
+        .unwrap_or(if config.binary_info.is_cargo_subcommand {
+            "Usage: {bin} <cargo-command> [args...]"
+        } else if config.needy {
+            "Usage: {bin} <exit_code>"
+        } else {
+            "Usage: {bin} <command> [args ...]"
+        });
+    fill_template(&template.replace("{bin}", &bin), config)
+}
+
+/// Apply the configured exit code remapping, leaving unmapped codes as-is
+fn map_exit_code(exit_map: &HashMap<i32, i32>, exit_code: i32) -> i32 {
+    exit_map.get(&exit_code).copied().unwrap_or(exit_code)
+}
+
+/// Branch for a `--force-branch=<positive|negative|neutral>` value
+fn parse_force_branch(value: &str) -> Result<Branch, String> {
+    value.parse().map_err(|()| {
+        format!("Invalid branch '{value}'. Expected 'positive', 'negative' or 'neutral'")
+    })
+}
+
+/// Split off mommy's own `--config <path>` (or `--config=<path>`) flag, if
+/// it's the first argument
+fn parse_config_flag(args: &[String]) -> Result<(Option<&str>, &[String]), String> {
+    match args.first().map(String::as_str) {
+        Some("--config") => match args.get(1) {
+            Some(path) => Ok((Some(path.as_str()), &args[2..])),
+            None => Err("--config needs a path to a config file".to_string()),
+        },
+        Some(arg) if arg.starts_with("--config=") => {
+            let path = &arg["--config=".len()..];
+            if path.is_empty() {
+                return Err("--config needs a path to a config file".to_string());
+            }
+            Ok((Some(path), &args[1..]))
+        }
+        _ => Ok((None, args)),
+    }
+}
+
+/// Split off a leading `--dry-run <exit code>`, which skips running anything
+/// and answers as if a command had exited with that code
+fn parse_dry_run(args: &[String]) -> Result<(Option<i32>, &[String]), String> {
+    if args.first().is_none_or(|arg| arg != "--dry-run") {
+        return Ok((None, args));
+    }
+    let code_str = args
+        .get(1)
+        .ok_or("--dry-run needs an exit code to pretend with (e.g., 0 or 1)")?;
+    let code = code_str.parse().map_err(|_| {
+        format!("Invalid exit code '{code_str}' for --dry-run. Expected a number (e.g., 0 or 1)")
+    })?;
+    Ok((Some(code), &args[2..]))
+}
+
+/// Whether a `--format=json` (or the default `--format=text`) value asks for
+/// JSON output
+fn parse_format(format: &str) -> Result<bool, String> {
+    match format {
+        "json" => Ok(true),
+        "text" => Ok(false),
+        _ => Err(format!(
+            "Invalid format '{format}'. Expected 'text' or 'json'"
+        )),
+    }
+}
+
+/// Split off mommy's own `-q`/`--quiet` or `--no-quiet` flag, if it's the
+/// first argument
+fn strip_quiet_flag(args: &[String]) -> (Option<bool>, &[String]) {
+    match args.first().map(String::as_str) {
+        Some("-q" | "--quiet") => (Some(true), &args[1..]),
+        Some("--no-quiet") => (Some(false), &args[1..]),
+        _ => (None, args),
+    }
+}
+
+/// Whether to stay quiet: a flag on the command line wins either way,
+/// otherwise `QUIET` decides
+fn resolve_quiet(from_config: bool, from_flag: Option<bool>) -> bool {
+    from_flag.unwrap_or(from_config)
+}
+
+/// Mommy's own flags from before the command. `--format` and
+/// `--force-branch` values are checked once mommy knows it isn't disabled
+#[derive(Debug, Default, PartialEq)]
+struct LeadingFlags<'a> {
+    config: Option<&'a str>,
+    format: Option<&'a str>,
+    dry_run: Option<i32>,
+    quiet: Option<bool>,
+    color: Option<ColorMode>,
+    force_branch: Option<&'a str>,
+}
+
+/// Split off mommy's own flags, in any order, up to the first argument that
+/// isn't one. Everything from there on is the command's, so a wrapped tool's
+/// flags (`mommy grep -q foo`, `mommy ls --color=auto`) pass through
+fn parse_leading_flags(mut args: &[String]) -> Result<(LeadingFlags<'_>, &[String]), String> {
+    let mut flags = LeadingFlags::default();
+    while let Some(first) = args.first() {
+        if let Some(format) = first.strip_prefix("--format=") {
+            flags.format = Some(format);
+            args = &args[1..];
+        } else if let Some(branch) = first.strip_prefix("--force-branch=") {
+            flags.force_branch = Some(branch);
+            args = &args[1..];
+        } else if let (Some(path), rest) = parse_config_flag(args)? {
+            flags.config = Some(path);
+            args = rest;
+        } else if let (Some(code), rest) = parse_dry_run(args)? {
+            flags.dry_run = Some(code);
+            args = rest;
+        } else if let (Some(quiet), rest) = strip_quiet_flag(args) {
+            flags.quiet = Some(quiet);
+            args = rest;
+        } else if let (Some(color), rest) = parse_color_mode(args) {
+            flags.color = Some(color);
+            args = rest;
+        } else {
+            break;
         }
-        _ => default_template,
     }
+    Ok((flags, args))
+}
+
+/// Mommy's own informational flags
+#[derive(Debug, PartialEq)]
+enum InfoFlag {
+    Help,
+    Version,
+    ListMoods,
+    Init { force: bool },
+}
+
+/// Check for `--help`/`-h`, `--version`/`-V` or `--list-moods`. Like the quiet flag, only the
+/// very first argument counts, so `mommy ls --help` still runs `ls --help`
+fn parse_info_flag(args: &[String]) -> Option<InfoFlag> {
+    match args.first().map(String::as_str) {
+        Some("-h" | "--help") => Some(InfoFlag::Help),
+        Some("-V" | "--version") => Some(InfoFlag::Version),
+        Some("--list-moods") => Some(InfoFlag::ListMoods),
+        Some("--init") => Some(InfoFlag::Init {
+            force: args.get(1).is_some_and(|arg| arg == "--force"),
+        }),
+        _ => None,
+    }
+}
+
+/// `--help` text, in the voice of whichever role mommy is running as
+fn help_text(config: &ConfigMommy, program: &str) -> String {
+    let role = &config.binary_info.role;
+    let prefix = get_env_prefix_from_binary(&config.binary_info);
+    format!(
+        "{usage}
+
+{role} runs your command and tells you how it went~
+
+Options (before the command, in any order):
+  -q, --quiet                  run the command without a word from {role}
+  --no-quiet                   let {role} talk even when QUIET is set
+  --config <path>              use this config file instead of the default one
+  --format=<format>            text (default), or json for one JSON object on stdout
+  --dry-run <code>             run nothing, answer as if a command exited with code
+  --force-branch=<branch>      always use positive, negative or neutral lines
+  --color=<when>, --no-color   color output: always, never or auto
+  -h, --help                   show this help
+  -V, --version                show the version
+  --list-moods                 list the moods you can pick from
+  --init [--force]             write a starter config and affirmations file
+
+Commands:
+  validate [<path>]            check your configuration, or one affirmations file
+  i mean <role>                copy {role} to a binary with another role name
+
+{role} is configured through {prefix}_* environment variables, see the README",
+        usage = usage_line(config, program),
+    )
 }
 
-/// Check if quiet mode is enabled from command line arguments
-fn is_quiet_mode_enabled(args: &[String]) -> bool {
-    args.iter().any(|arg| arg == "--quiet" || arg == "-q")
+/// Split off mommy's own `--color=always|never|auto` or `--no-color` flag,
+/// if it's the first argument
+fn parse_color_mode(args: &[String]) -> (Option<ColorMode>, &[String]) {
+    let mode = match args.first().map(String::as_str) {
+        Some("--color=always") => ColorMode::Always,
+        Some("--color=never" | "--no-color") => ColorMode::Never,
+        Some("--color=auto") => ColorMode::Auto,
+        _ => return (None, args),
+    };
+    (Some(mode), &args[1..])
 }
 
 /// Check if a string is a simple word safe for alias expansion
@@ -38,15 +526,78 @@ fn is_safe_for_alias(s: &str) -> bool {
             .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
 }
 
-/// Check if the command contains "i mean" for role transformation
-fn check_role_transformation(args: &[String]) -> Option<&str> {
+/// Parse an aliases file of `name=command` lines. A leading `alias ` and
+/// quotes around the command are allowed, so simple shell alias files work
+/// as is; anything else (comments, functions, ...) is skipped
+fn parse_alias_file(contents: &str) -> HashMap<String, Vec<String>> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix("alias ").unwrap_or(line).trim_start();
+            let (name, value) = line.split_once('=')?;
+            if !is_safe_for_alias(name) {
+                return None;
+            }
+            let value = ['\'', '"']
+                .iter()
+                .find_map(|&quote| value.strip_prefix(quote)?.strip_suffix(quote))
+                .unwrap_or(value);
+            let words: Vec<String> = value.split_whitespace().map(str::to_string).collect();
+            (!words.is_empty()).then(|| (name.to_string(), words))
+        })
+        .collect()
+}
+
+/// Replace the command's first word with its alias, if it has one. The
+/// result isn't expanded again
+fn expand_alias(args: &[&str], aliases: &HashMap<String, Vec<String>>) -> Vec<String> {
+    let Some((first, rest)) = args.split_first() else {
+        return Vec::new();
+    };
+    let head = aliases
+        .get(*first)
+        .cloned()
+        .unwrap_or_else(|| vec![first.to_string()]);
+    head.into_iter()
+        .chain(rest.iter().map(|arg| arg.to_string()))
+        .collect()
+}
+
+/// Shells that understand `-c`, `.` and `eval` the POSIX way
+const POSIX_SHELLS: &[&str] = &["sh", "bash", "zsh", "dash", "ksh", "mksh", "ash", "yash"];
+
+/// Shell to run aliased commands with: `shell_env` (the user's `$SHELL`) if
+/// it's POSIX-compatible, else bash if there is one, else sh. Returns the
+/// program and its base args
+fn pick_shell(shell_env: Option<&str>, has_bash: bool) -> (String, Vec<String>) {
+    let program = shell_env
+        .filter(|shell| shell_name(shell).is_some_and(|name| POSIX_SHELLS.contains(&name.as_str())))
+        .map(str::to_string)
+        .unwrap_or_else(|| if has_bash { "bash" } else { "sh" }.to_string());
+    (program, vec!["-c".to_string()])
+}
+
+/// Whether `program` can be found on `PATH`
+fn in_path(program: &str) -> bool {
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+/// Shell to run aliased commands with, from `$SHELL` (see `pick_shell`)
+fn resolve_shell() -> (String, Vec<String>) {
+    pick_shell(env::var("SHELL").ok().as_deref(), in_path("bash"))
+}
+
+/// Check if the command contains "i mean" for role transformation.
+/// Only a leading "i mean" counts unless `anywhere` is set, so the words can
+/// still show up inside a wrapped command
+fn check_role_transformation(args: &[String], anywhere: bool) -> Option<&str> {
     // Look for pattern: "mommy i mean daddy" or similar
-    for i in 0..args.len().saturating_sub(2) {
-        if args[i] == "i" && args[i + 1] == "mean" && i + 2 < args.len() {
-            return Some(&args[i + 2]);
-        }
-    }
-    None
+    args.windows(3)
+        .take(if anywhere { usize::MAX } else { 1 })
+        .find(|window| window[0] == "i" && window[1] == "mean")
+        .map(|window| window[2].as_str())
 }
 
 /// Perform role transformation by copying the binary
@@ -92,21 +643,77 @@ fn perform_role_transformation(
     }
 
     println!("Created new binary: {}", new_path.display());
-    println!("You can now use: {new_name}");
+    if binary_info.is_cargo_subcommand {
+        println!("You can now use: cargo {new_role}");
+    } else {
+        println!("You can now use: {new_role}");
+    }
+    // The copy sits next to this binary, which isn't necessarily on PATH
+    if !in_path(&new_name) {
+        println!("Add {} to your PATH to use it anywhere", parent.display());
+    }
 
     Ok(())
 }
 
+/// Whether this mommy is nested too deep to run anything
+fn recursion_exceeded(config: &ConfigMommy) -> bool {
+    config.recursion_depth >= config.recursion_max
+}
+
+/// Whether a mommy at this nesting depth gets to speak. With
+/// `RECURSION_QUIET` only the outermost one does, so nested cargo calls don't
+/// pile up affirmations
+fn depth_speaks(depth: usize, recursion_quiet: bool) -> bool {
+    depth == 0 || !recursion_quiet
+}
+
+/// Pass the incremented recursion counter (and the chain seed, if any) down
+/// to the child, unless the child's environment has to stay untouched
+fn propagate_recursion(cmd: &mut Command, config: &ConfigMommy, prefix: &str) {
+    if config.no_env_propagate {
+        return;
+    }
+    cmd.env(
+        format!("{prefix}_RECURSION_LIMIT"),
+        (config.recursion_depth + 1).to_string(),
+    );
+    if let Some(seed) = config.chain_seed {
+        cmd.env(format!("{prefix}_CHAIN_SEED"), seed.to_string());
+    }
+}
+
+/// Arguments for the wrapped command: "please" is filtered out and the rest
+/// converted to &str in a single pass
+fn forwarded_args(command_args: &[String]) -> Vec<&str> {
+    command_args
+        .iter()
+        .filter(|arg| *arg != "please")
+        .map(String::as_str)
+        .collect()
+}
+
+/// Arguments for the wrapped command when mommy is disabled: "please" is
+/// passed on like any other word
+fn passthrough_args(command_args: &[String]) -> Vec<&str> {
+    command_args.iter().map(String::as_str).collect()
+}
+
+/// Run the wrapped command and report how it finished
 fn execute_command(
     config: &ConfigMommy,
     filtered_args: &[&str],
-) -> Result<i32, Box<dyn std::error::Error>> {
+) -> Result<Finished, Box<dyn std::error::Error>> {
     if config.needy {
         let code_str = filtered_args
             .first()
             .ok_or_else(|| "Missing exit code".to_string())?;
-        code_str.parse().map_err(|_| {
-            format!("Invalid exit code '{code_str}'. Expected a number (e.g., 0 or 1)").into()
+        let code = code_str.parse().map_err(|_| {
+            format!("Invalid exit code '{code_str}'. Expected a number (e.g., 0 or 1)")
+        })?;
+        Ok(Finished {
+            exit_code: code,
+            ..Finished::default()
         })
     } else if config.binary_info.is_cargo_subcommand {
         // Running as cargo subcommand - execute cargo with the provided args
@@ -114,23 +721,15 @@ fn execute_command(
             return Err("No cargo command provided".into());
         }
 
-        // Increment recursion counter
-        let new_recursion = config.recursion_limit + 1;
-
-        let status = Command::new("cargo")
-            .args(filtered_args)
-            .env("CARGO_MOMMY_RECURSION_LIMIT", new_recursion.to_string())
-            .status()?;
-
-        Ok(status.code().unwrap_or(1))
+        let mut cmd = Command::new("cargo");
+        cmd.args(filtered_args);
+        propagate_recursion(&mut cmd, config, "CARGO_MOMMY");
+        Ok(run_status(&mut cmd, config.capture, config.timeout)?)
     } else {
-        // Running as shell command wrapper
-
-        // Increment recursion counter
-        let new_recursion = config.recursion_limit + 1;
-
-        if let Some(ref aliases_path) = config.aliases {
-            // Use bash -c for alias support, but safely quote arguments to prevent
+        // Running as shell command wrapper. With ALIASES_SHELL the aliases
+        // file is sourced by a real shell
+        if let Some(aliases_path) = config.aliases.as_ref().filter(|_| config.aliases_shell) {
+            // Use the shell's -c for alias support, but safely quote arguments to prevent
             // injection. To allow alias expansion, we must use `eval` because
             // aliases are expanded when a command is read, not when it is
             // executed.
@@ -147,35 +746,72 @@ fn execute_command(
                 }
             }
 
+            let (shell, shell_args) = resolve_shell();
+            // Non-interactive bash only expands aliases when told to
+            let expand_aliases = if shell_name(&shell).as_deref() == Some("bash") {
+                "shopt -s expand_aliases; "
+            } else {
+                ""
+            };
             let run_command = format!(
-                "shopt -s expand_aliases; . {}; eval {}",
+                "{expand_aliases}. {}; eval {}",
                 shell_quote(aliases_path),
                 shell_quote(&cmd_to_eval)
             );
 
-            let status = Command::new("bash")
-                .arg("-c")
-                .arg(&run_command)
-                .env("SHELL_MOMMY_RECURSION_LIMIT", new_recursion.to_string())
-                .status()?;
-
-            Ok(status.code().unwrap_or(1))
+            let mut cmd = Command::new(&shell);
+            cmd.args(&shell_args).arg(&run_command);
+            propagate_recursion(&mut cmd, config, "SHELL_MOMMY");
+            Ok(run_status(&mut cmd, config.capture, config.timeout)?)
         } else {
-            // No aliases needed - execute command directly without bash -c
-            if filtered_args.is_empty() {
+            // Expand aliases ourselves, then execute directly without a shell
+            let args = match config.aliases {
+                Some(ref aliases_path) => {
+                    let contents = fs::read_to_string(aliases_path)
+                        .map_err(|e| format!("Can't read the aliases file {aliases_path}: {e}"))?;
+                    expand_alias(filtered_args, &parse_alias_file(&contents))
+                }
+                None => filtered_args.iter().map(|arg| arg.to_string()).collect(),
+            };
+            if args.is_empty() {
                 return Err("No command provided".into());
             }
 
-            let status = Command::new(filtered_args[0])
-                .args(&filtered_args[1..])
-                .env("SHELL_MOMMY_RECURSION_LIMIT", new_recursion.to_string())
-                .status()?;
-
-            Ok(status.code().unwrap_or(1))
+            let mut cmd = Command::new(&args[0]);
+            cmd.args(&args[1..]);
+            propagate_recursion(&mut cmd, config, "SHELL_MOMMY");
+            Ok(run_status(&mut cmd, config.capture, config.timeout)?)
         }
     }
 }
 
+/// Run the wrapped command untouched (no env, no aliases, no "please"
+/// filtering) and hand back its exit code
+fn run_passthrough(
+    config: &ConfigMommy,
+    command_args: &[&str],
+) -> Result<i32, Box<dyn std::error::Error>> {
+    if config.needy {
+        let code_str = command_args.first().ok_or("Missing exit code")?;
+        return code_str
+            .parse()
+            .map_err(|_| format!("Invalid exit code '{code_str}'").into());
+    }
+
+    let mut cmd = if config.binary_info.is_cargo_subcommand {
+        let mut cmd = Command::new("cargo");
+        cmd.args(command_args);
+        cmd
+    } else {
+        let (program, rest) = command_args.split_first().ok_or("No command provided")?;
+        let mut cmd = Command::new(program);
+        cmd.args(rest);
+        cmd
+    };
+
+    Ok(exit_code_from_status(cmd.status()?))
+}
+
 #[cfg(feature = "beg")]
 fn handle_begging(
     command_args: &[String],
@@ -187,18 +823,21 @@ fn handle_begging(
         if has_please {
             state.mood = crate::state::Mood::Chill;
             if let Err(e) = state.save() {
-                eprintln!("mommy failed to remember how she feels: {e}");
+                warn(
+                    config,
+                    &format!("mommy failed to remember how she feels: {e}"),
+                );
             }
             let output = fill_template("{roles} forgives {pronouns} {little}~ {emotes}", config);
-            let styled_output = output.style(random_style_pick(config));
-            graceful_print(styled_output);
+            let styled_output = output.style(output_style(config, None));
+            graceful_print(styled_output, config.output);
         } else {
             let output = fill_template(
                 "{roles} is waiting for {pronouns} {little} to say please~ {emotes}",
                 config,
             );
-            let styled_output = output.style(random_style_pick(config));
-            graceful_print(styled_output);
+            let styled_output = output.style(output_style(config, None));
+            graceful_print(styled_output, config.output);
             exit(1);
         }
     }
@@ -206,7 +845,10 @@ fn handle_begging(
 }
 
 #[cfg(feature = "beg")]
-fn update_begging_state(exit_code: i32) -> Result<(), Box<dyn std::error::Error>> {
+fn update_begging_state(
+    exit_code: i32,
+    config: &ConfigMommy,
+) -> Result<(), Box<dyn std::error::Error>> {
     let mut state = crate::state::State::load()?;
     state.mood = if exit_code == 0 {
         crate::state::Mood::Chill
@@ -214,172 +856,1142 @@ fn update_begging_state(exit_code: i32) -> Result<(), Box<dyn std::error::Error>
         crate::state::Mood::Angry
     };
     if let Err(e) = state.save() {
-        eprintln!("mommy failed to remember how she feels: {e}");
+        warn(
+            config,
+            &format!("mommy failed to remember how she feels: {e}"),
+        );
     }
     Ok(())
 }
 
-fn print_affirmation(
-    exit_code: i32,
+/// Load the affirmations for a mood from the custom file if configured,
+/// otherwise from the embedded defaults. Files that can't be used are
+/// reported in `warnings` for the caller to show or drop
+fn load_mood_affirmations(
     config: &ConfigMommy,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Skip output if quiet mode is enabled
-    if config.quiet {
-        return Ok(());
+    mood: &str,
+    warnings: &mut Vec<String>,
+) -> Option<AffirmationData<'static>> {
+    if let Some(ref dir) = config.pack {
+        load_pack_affirmations_with_mood_mixing(
+            dir,
+            mood,
+            config.mood_mixing,
+            config.max_affirmations_bytes,
+            warnings,
+        )
+    } else if let Some(ref value) = config.affirmations {
+        let paths = split_affirmation_paths(value);
+        let load = if config.affirmations_merge {
+            load_merged_affirmations_with_mood_mixing
+        } else if paths.len() > 1 {
+            load_custom_affirmations_multi
+        } else {
+            return load_custom_affirmations_with_mood_mixing(
+                value,
+                mood,
+                config.mood_mixing,
+                config.max_affirmations_bytes,
+                warnings,
+            );
+        };
+        load(
+            &paths,
+            mood,
+            config.mood_mixing,
+            config.max_affirmations_bytes,
+            warnings,
+        )
+    } else {
+        load_affirmations_with_mood_mixing(mood, config.mood_mixing)
     }
+}
 
-    // Optimization: If the command succeeded and we only want to show negative
-    // affirmations, we can skip loading affirmations entirely.
-    if exit_code == 0 && config.only_negative {
-        return Ok(());
+/// Load the affirmations of the first mood with lines for `branch`, trying
+/// the picked mood and then the `MOOD_FALLBACK` chain in order. If none has
+/// any, the picked mood loads as usual (falling back to the defaults)
+fn load_mood_chain_affirmations(
+    config: &ConfigMommy,
+    mood: &str,
+    branch: Branch,
+    warnings: &mut Vec<String>,
+) -> Option<AffirmationData<'static>> {
+    if !config.mood_fallback.is_empty() {
+        let chain = std::iter::once(mood).chain(config.mood_fallback.iter().map(String::as_str));
+        for candidate in chain {
+            let Some(affirmations) = load_mood_affirmations(config, candidate, warnings) else {
+                continue;
+            };
+            if affirmations.mood() == Some(candidate) && !affirmations.pool(branch).is_empty() {
+                return Some(affirmations);
+            }
+        }
     }
-    // Use pre-parsed moods vector
-    let selected_mood = random_vec_pick(&config.moods).unwrap_or("chill");
-
-    let affirmations: Option<AffirmationData> = if let Some(ref path) = config.affirmations {
-        load_custom_affirmations_with_mood_mixing(path, selected_mood, config.mood_mixing)
-    } else {
-        load_affirmations_with_mood_mixing(selected_mood, config.mood_mixing)
-    };
+    load_mood_affirmations(config, mood, warnings)
+}
 
-    // Use const str instead of Vec allocation
-    const AFFIRMATIONS_ERROR: &str = "{roles} failed to load any affirmations, {little}~ {emotes}";
+/// Show each warning collected while loading affirmations once, however many
+/// moods were tried
+fn warn_each(config: &ConfigMommy, warnings: &[String]) {
+    for (i, message) in warnings.iter().enumerate() {
+        if !warnings[..i].contains(message) {
+            warn(config, message);
+        }
+    }
+}
 
-    let (template, _affirmation_type) = match (exit_code == 0, config.only_negative) {
-        (true, false) => (
-            choose_template(
-                affirmations
-                    .as_ref()
-                    .map(super::affirmations::AffirmationData::positive),
-                AFFIRMATIONS_ERROR,
-            ),
-            "positive",
-        ),
-        (false, _) => (
-            choose_template(
-                affirmations
-                    .as_ref()
-                    .map(super::affirmations::AffirmationData::negative),
-                AFFIRMATIONS_ERROR,
-            ),
-            "negative",
-        ),
-        _ => return Ok(()),
-    };
+/// Draw `n` templates through the regular selection and count how often
+/// each line of the pool came up, in pool order
+fn sample_templates(templates: &[Affirmation], n: usize) -> Vec<(&str, usize)> {
+    let candidates: Vec<&Affirmation> = templates.iter().collect();
+    let mut counts: Vec<(&str, usize)> = templates.iter().map(|t| (t.text.as_str(), 0)).collect();
+    for _ in 0..n {
+        let picked = choose_template(&candidates, "");
+        if let Some(entry) = counts.iter_mut().find(|(t, _)| *t == picked) {
+            entry.1 += 1;
+        }
+    }
+    counts
+}
 
-    let output = fill_template(template, config);
-    let styled_output = output.style(random_style_pick(config));
-    graceful_print(styled_output);
+/// Render sample counts as a text histogram, one pool line per row
+fn format_histogram(counts: &[(&str, usize)]) -> String {
+    const BAR_WIDTH: usize = 40;
+    let max = counts.iter().map(|(_, c)| *c).max().unwrap_or(0).max(1);
+    counts
+        .iter()
+        .map(|(template, count)| {
+            let bar = "#".repeat(count * BAR_WIDTH / max);
+            format!(
+                "{count:>6} {bar:<BAR_WIDTH$} {}",
+                template.replace('\n', " ")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
-    Ok(())
+/// Parse `sample [--n N] [--mood MOOD] [--negative]` arguments
+fn parse_sample_args(args: &[String]) -> Result<(usize, Option<&str>, bool), String> {
+    let mut n = 1000;
+    let mut mood = None;
+    let mut negative = false;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--n" => {
+                let value = iter.next().ok_or("Missing value for --n")?;
+                n = value
+                    .parse()
+                    .map_err(|_| format!("Invalid sample count '{value}'"))?;
+            }
+            "--mood" => mood = Some(iter.next().ok_or("Missing value for --mood")?.as_str()),
+            "--negative" => negative = true,
+            other => return Err(format!("Unknown sample option '{other}'")),
+        }
+    }
+    Ok((n, mood, negative))
 }
 
-pub fn mommy() -> Result<i32, Box<dyn std::error::Error>> {
-    let mut config = load_config();
-    let is_cargo_command = config.binary_info.is_cargo_subcommand;
+/// Hidden `sample` subcommand: print how often each affirmation gets picked
+fn run_sample(args: &[String], config: &ConfigMommy) -> Result<i32, Box<dyn std::error::Error>> {
+    let (n, mood, negative) = parse_sample_args(args)?;
+    let mood = mood
+        .or_else(|| config.moods.first().map(String::as_str))
+        .unwrap_or("chill");
+    let mut warnings = Vec::new();
+    let affirmations = load_mood_affirmations(config, mood, &mut warnings);
+    warn_each(config, &warnings);
+    let affirmations = affirmations.ok_or("Failed to load any affirmations")?;
+    let pool = if negative {
+        affirmations.negative()
+    } else {
+        affirmations.positive()
+    };
 
-    // Check recursion limit
-    if config.recursion_limit >= RECURSION_LIMIT {
-        eprintln!("Recursion limit exceeded! Mommy is stuck in a loop~");
-        return Ok(2); // Special exit code for recursion overflow
-    }
+    print_paged(&format_histogram(&sample_templates(pool, n)), config.pager);
+    Ok(0)
+}
 
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        let role = &config.binary_info.role;
-        let usage = if is_cargo_command {
-            format!("cargo {role} <cargo-command> [args...]")
-        } else if config.needy {
-            format!("{} <exit_code>", args[0])
-        } else {
-            format!("{} <command> [args ...]", args[0])
-        };
-        eprintln!("Usage: {usage}");
-        exit(1);
+/// Average time per call of `f` over `iterations` calls, in microseconds
+fn time_micros(iterations: u32, mut f: impl FnMut()) -> f64 {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
     }
+    start.elapsed().as_secs_f64() * 1_000_000.0 / f64::from(iterations.max(1))
+}
 
-    // Check for quiet mode
-    config.quiet = is_quiet_mode_enabled(&args);
+/// Hidden `bench` subcommand: time parsing the embedded and custom
+/// affirmations and selecting templates, `iterations` times each
+fn run_bench(config: &ConfigMommy, iterations: u32, out: &mut impl Write) -> io::Result<()> {
+    let embedded = time_micros(iterations, || {
+        black_box(check_affirmations(black_box(EMBEDDED_AFFIRMATIONS_JSON)).is_ok());
+    });
+    writeln!(out, "embedded parse:  {embedded:>10.2} µs")?;
 
-    // Check for role transformation
-    if let Some(new_role) = check_role_transformation(&args) {
-        perform_role_transformation(new_role, &config.binary_info)?;
-        return Ok(0);
+    if let Some(ref path) = config.affirmations {
+        let custom = time_micros(iterations, || {
+            black_box(load_custom_affirmations_with_mood_mixing(
+                path,
+                "chill",
+                false,
+                config.max_affirmations_bytes,
+                &mut Vec::new(),
+            ));
+        });
+        writeln!(out, "custom parse:    {custom:>10.2} µs")?;
     }
 
-    // Skip the binary name for processing
-    let mut command_args = &args[1..];
+    let affirmations = load_affirmations_with_mood_mixing("chill", false);
+    let mut exit_code = 0;
+    let selection = time_micros(iterations, || {
+        exit_code ^= 1;
+        black_box(select_template(
+            exit_code,
+            config,
+            affirmations.as_ref(),
+            &[],
+            None,
+        ));
+    });
+    writeln!(out, "selection:       {selection:>10.2} µs")?;
+    Ok(())
+}
 
-    // If running as cargo subcommand, skip "cargo" if it's the first arg
-    if is_cargo_command && !command_args.is_empty() && command_args[0] == "cargo" {
-        command_args = &command_args[1..];
+/// Index of the mood after the one at `last` (as stored by `MOOD_ROTATE`),
+/// wrapping around. A missing or corrupt index starts over at 0
+fn next_mood_index(last: Option<&str>, len: usize) -> usize {
+    match last.and_then(|idx| idx.trim().parse::<usize>().ok()) {
+        Some(idx) if len > 0 => (idx + 1) % len,
+        _ => 0,
     }
+}
 
-    // Handle "please" for begging mode (if enabled)
-    #[cfg(feature = "beg")]
-    handle_begging(command_args, &config)?;
+/// Next mood in `MOOD_ROTATE` order, remembering where the rotation is at
+fn rotate_mood(config: &ConfigMommy, rotation: &dyn TextStore) -> String {
+    let idx = next_mood_index(rotation.load().as_deref(), config.moods.len());
+    if let Err(e) = rotation.store(&idx.to_string()) {
+        warn(config, &format!("mommy failed to remember her mood: {e}"));
+    }
+    config.moods[idx].clone()
+}
 
-    // Filter out "please" and convert to &str in a single pass
-    let filtered_args: Vec<&str> = command_args
-        .iter()
-        .filter(|arg| *arg != "please")
-        .map(std::string::String::as_str)
-        .collect();
+/// Pick the mood for this run from the pre-parsed moods vector, at random or
+/// in order with `MOOD_ROTATE`. Without an explicit MOODS, `REMEMBER_MOOD`
+/// reuses the mood of the last run instead
+fn select_mood(config: &ConfigMommy, store: &dyn TextStore, rotation: &dyn TextStore) -> String {
+    let remembered = if config.remember_mood && !config.moods_explicit {
+        store.load()
+    } else {
+        None
+    };
+    let mood = remembered.unwrap_or_else(|| {
+        if config.mood_rotate && !config.moods.is_empty() {
+            return rotate_mood(config, rotation);
+        }
+        random_vec_pick(&config.moods)
+            .unwrap_or("chill")
+            .to_string()
+    });
 
-    let exit_code = execute_command(&config, &filtered_args)?;
+    if config.remember_mood {
+        if let Err(e) = store.store(&mood) {
+            warn(config, &format!("mommy failed to remember her mood: {e}"));
+        }
+    }
+    mood
+}
 
-    // Update begging state (if enabled)
-    #[cfg(feature = "beg")]
-    update_begging_state(exit_code)?;
+/// Number of runs during which `SHOWCASE` cycles through unseen lines
+const SHOWCASE_RUNS: u32 = 20;
 
-    print_affirmation(exit_code, &config)?;
+/// Record a line shown during the `SHOWCASE` warm-up. Showing a line again
+/// means its pool was exhausted, so a new cycle starts
+fn record_showcase(showcase: &mut Showcase, template: &str) {
+    if showcase.seen.iter().any(|s| s == template) {
+        showcase.seen.clear();
+    }
+    showcase.seen.push(template.to_string());
+    showcase.runs += 1;
+}
 
-    Ok(exit_code)
+/// Branch to draw from: the forced one if any, otherwise based on the exit
+/// code, with `NEUTRAL_CODES` failures on the neutral branch
+fn resolve_branch(exit_code: i32, config: &ConfigMommy) -> Branch {
+    config.force_branch.unwrap_or_else(|| {
+        if exit_code != 0 && config.neutral_codes.contains(&exit_code) {
+            Branch::Neutral
+        } else {
+            Branch::for_exit_code(exit_code)
+        }
+    })
 }
 
-#[cfg(test)]
-mod tests {
+/// Pick the template for this run from the loaded affirmations, or `None` if
+/// mommy should stay silent. Lines in `seen` and the `last` line shown are
+/// avoided while others remain
+fn select_template(
+    exit_code: i32,
+    config: &ConfigMommy,
+    affirmations: Option<&AffirmationData>,
+    seen: &[String],
+    last: Option<&str>,
+) -> Option<String> {
+    // Use const str instead of Vec allocation
+    const AFFIRMATIONS_ERROR: &str = "{roles} failed to load any affirmations, {little}~ {emotes}";
+
+    let branch = resolve_branch(exit_code, config);
+    let pool = match branch {
+        Branch::Positive if config.only_negative => return None,
+        Branch::Negative | Branch::Neutral if config.only_positive => return None,
+        _ => affirmations.map(|a| a.pool(branch)),
+    };
+
+    // Interruptions and exit codes with a specific meaning get their own line,
+    // unless a branch was forced. The mood's own lines for the code come first
+    if config.force_branch.is_none() {
+        let mut by_code: Vec<&Affirmation> = affirmations
+            .map_or(&[][..], |a| a.for_code(exit_code))
+            .iter()
+            .collect();
+        if !by_code.is_empty() {
+            prefer_unseen(&mut by_code, seen);
+            return Some(choose_template_no_repeat(&by_code, AFFIRMATIONS_ERROR, last).to_string());
+        }
+        // NEUTRAL_CODES get the mood's softer lines, if it has any
+        if branch == Branch::Neutral {
+            let mut neutral: Vec<&Affirmation> = affirmations
+                .map_or(&[][..], AffirmationData::neutral)
+                .iter()
+                .collect();
+            if !neutral.is_empty() {
+                prefer_unseen(&mut neutral, seen);
+                return Some(
+                    choose_template_no_repeat(&neutral, AFFIRMATIONS_ERROR, last).to_string(),
+                );
+            }
+        }
+        if config.timed_out {
+            return Some(TIMED_OUT.to_string());
+        }
+        if config.interrupted {
+            return Some(INTERRUPTED.to_string());
+        }
+        if let Some(template) =
+            exit_code_template(exit_code, config.binary_info.is_cargo_subcommand)
+        {
+            return Some(template.to_string());
+        }
+        if exit_code != 0 && config.last_stderr.as_deref().is_some_and(mentions_error) {
+            return Some(STDERR_ERROR.to_string());
+        }
+    }
+
+    // Saying "please" gets the soft begging lines for either outcome
+    if config.begging {
+        let mut begging: Vec<&Affirmation> = affirmations
+            .map_or(&[][..], AffirmationData::begging)
+            .iter()
+            .collect();
+        if !begging.is_empty() {
+            prefer_unseen(&mut begging, seen);
+            return Some(choose_template_no_repeat(&begging, AFFIRMATIONS_ERROR, last).to_string());
+        }
+    }
+
+    let mut candidates: Vec<&Affirmation> = pool.unwrap_or_default().iter().collect();
+    prefer_shell(&mut candidates, config.shell.as_deref());
+    if let Some(persona) = random_vec_pick(&config.personas) {
+        prefer_persona(&mut candidates, persona, config.persona_strict);
+    }
+    if branch == Branch::Negative && exit_code != 0 && config.severity_match {
+        prefer_severity(&mut candidates, Severity::for_exit_code(exit_code));
+    }
+    prefer_unseen(&mut candidates, seen);
+
+    let combo = if branch == Branch::Positive && config.combo {
+        affirmations.and_then(|a| combo_template(a.prefix(), a.suffix()))
+    } else {
+        None
+    };
+    Some(combo.unwrap_or_else(|| {
+        choose_template_no_repeat(&candidates, AFFIRMATIONS_ERROR, last).to_string()
+    }))
+}
+
+/// Roll whether mommy says anything this run. Full chance skips the roll so
+/// the random sequence is unchanged
+fn should_speak(chance: f32) -> bool {
+    chance >= 1.0 || random::f32() < chance
+}
+
+fn print_affirmation(
+    exit_code: i32,
+    config: &ConfigMommy,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Skip output if quiet mode is enabled, an outer mommy already speaks for
+    // this run, or mommy doesn't feel like talking this time
+    if config.quiet
+        || !depth_speaks(config.recursion_depth, config.recursion_quiet)
+        || !should_speak(config.chance)
+    {
+        return Ok(());
+    }
+
+    // Optimization: If the branch is filtered out by only_negative or
+    // only_positive, we can skip loading affirmations entirely.
+    match resolve_branch(exit_code, config) {
+        Branch::Positive if config.only_negative => return Ok(()),
+        Branch::Negative | Branch::Neutral if config.only_positive => return Ok(()),
+        _ => {}
+    }
+
+    if config.ci && !config.json_output {
+        graceful_print(ci_line(&config.binary_info.role, exit_code), config.output);
+        return Ok(());
+    }
+
+    // Within a consistent chain, mood and style come from the shared seed while
+    // the text still varies per layer
+    let text_seed = config.chain_seed.map(|seed| {
+        let text_seed = fastrand::u64(..);
+        fastrand::seed(seed);
+        text_seed
+    });
+
+    let selected_mood = select_mood(
+        config,
+        &FileTextStore::last_mood(config.scope),
+        &FileTextStore::mood_index(config.scope),
+    );
+    let selected_mood = selected_mood.as_str();
+    // Loaded before picking the style so the mood's own color can apply
+    let mut warnings = Vec::new();
+    let affirmations = load_mood_chain_affirmations(
+        config,
+        selected_mood,
+        resolve_branch(exit_code, config),
+        &mut warnings,
+    );
+    warn_each(config, &warnings);
+    for unknown in unknown_colors(config) {
+        warn(config, &format!("mommy: {unknown}, using the default"));
+    }
+    let style = output_style(
+        config,
+        affirmations.as_ref().and_then(AffirmationData::color),
+    );
+
+    if let Some(seed) = text_seed {
+        fastrand::seed(seed);
+    }
+
+    // During the showcase warm-up, cycle through lines not shown yet
+    let showcase_store = FileShowcaseStore::new(config.scope);
+    let mut showcase = config
+        .showcase
+        .then(|| showcase_store.load())
+        .filter(|showcase| showcase.runs < SHOWCASE_RUNS);
+    let seen = showcase.as_ref().map_or(&[][..], |s| s.seen.as_slice());
+
+    // With NO_REPEAT, the line shown last time is avoided
+    let last_line_store = FileTextStore::last_line(config.scope);
+    let last = config.no_repeat.then(|| last_line_store.load()).flatten();
+
+    let Some(template) = select_template(
+        exit_code,
+        config,
+        affirmations.as_ref(),
+        seen,
+        last.as_deref(),
+    ) else {
+        return Ok(());
+    };
+    // Rendering leaves unknown `{?flag:text}` fragments out quietly, the
+    // binary is the one to point them out
+    for flag in unknown_placeholders(&template, config)
+        .iter()
+        .filter_map(|name| name.strip_prefix('?'))
+    {
+        warn(
+            config,
+            &format!("mommy doesn't know the template flag '{}'", flag.trim()),
+        );
+    }
+    if !config.capture && (template.contains("{warn_count") || template.contains("{error_count")) {
+        warn(
+            config,
+            "mommy only counts warnings and errors with CAPTURE=1, saying 0",
+        );
+    }
+
+    if config.no_repeat {
+        if let Err(e) = last_line_store.store(&template) {
+            warn(
+                config,
+                &format!("mommy failed to remember what she said last: {e}"),
+            );
+        }
+    }
+
+    if let Some(showcase) = &mut showcase {
+        record_showcase(showcase, &template);
+        if let Err(e) = showcase_store.store(showcase) {
+            warn(
+                config,
+                &format!("mommy failed to remember what she told you: {e}"),
+            );
+        }
+    }
+
+    let output = render_line(&template, config, exit_code, selected_mood);
+    if config.json_output {
+        let record = json_record(exit_code, config, selected_mood, &output)?;
+        graceful_print(record, config.output);
+    } else {
+        graceful_print(styled_line(&output, config, style), config.output);
+    }
+
+    Ok(())
+}
+
+/// Apply the picked style to a line, painting it with the `GRADIENT` colors
+/// instead of a single one when set and colors are on. A line with several
+/// emotes has the gradient spread over them rather than its characters
+fn styled_line(line: &str, config: &ConfigMommy, style: Style) -> String {
+    let gradient = config
+        .gradient
+        .as_deref()
+        .and_then(parse_gradient)
+        .filter(|_| colorize_output(config));
+    match gradient {
+        Some((start, end)) => paint_emote_gradient(line, &config.emotes, start, end, style)
+            .unwrap_or_else(|| paint_gradient(line, start, end).style(style).to_string()),
+        None => line.style(style).to_string(),
+    }
+}
+
+/// One affirmation as printed by `--format=json`
+#[derive(Serialize)]
+struct AffirmationRecord<'a> {
+    exit_code: i32,
+    mood: &'a str,
+    #[serde(rename = "type")]
+    branch: &'static str,
+    affirmation: &'a str,
+    role: &'a str,
+}
+
+/// The `--format=json` line for an affirmation, unstyled
+fn json_record(
+    exit_code: i32,
+    config: &ConfigMommy,
+    mood: &str,
+    affirmation: &str,
+) -> serde_json::Result<String> {
+    let branch = match resolve_branch(exit_code, config) {
+        Branch::Positive => "positive",
+        Branch::Negative => "negative",
+        Branch::Neutral => "neutral",
+    };
+    serde_json::to_string(&AffirmationRecord {
+        exit_code,
+        mood,
+        branch,
+        affirmation,
+        role: &config.binary_info.role,
+    })
+}
+
+/// Fill in a picked template and apply the output options
+fn render_line(template: &str, config: &ConfigMommy, exit_code: i32, mood: &str) -> String {
+    let mut output = fill_template_with_code(template, config, exit_code);
+    if config.normalize {
+        output = normalize_whitespace(&output);
+    }
+    if config.mood_prefix {
+        output = format!("{} {output}", mood_glyph(mood));
+    }
+    output
+}
+
+/// One affirmation for a command that exited with `exit_code`, styled unless
+/// colors are off (see `--color`). Unlike the binary, nothing is printed and
+/// no remembered state (mood, last line, showcase) is read or written. Empty
+/// if mommy would stay silent for this exit code
+///
+/// ```
+/// let mut config = shell_mommy::load_config();
+/// config.force_color = Some(false);
+/// config.only_negative = false;
+/// config.only_positive = false;
+/// assert!(!shell_mommy::affirm(0, &config).is_empty());
+///
+/// config.only_negative = true;
+/// assert!(shell_mommy::affirm(0, &config).is_empty());
+/// ```
+pub fn affirm(exit_code: i32, config: &ConfigMommy) -> String {
+    affirm_picked(exit_code, config)
+}
+
+/// `affirm` with every random pick (mood, line, emotes, style) drawn from
+/// `rng` instead of fastrand, so the host controls the randomness entirely.
+/// The same numbers give the same affirmation
+///
+/// ```
+/// let mut config = shell_mommy::load_config();
+/// config.force_color = Some(false);
+/// config.only_negative = false;
+/// let counter = || {
+///     let mut n = 0;
+///     move || {
+///         n += 1;
+///         n
+///     }
+/// };
+/// assert_eq!(
+///     shell_mommy::affirm_with_rng(1, &config, counter()),
+///     shell_mommy::affirm_with_rng(1, &config, counter()),
+/// );
+/// ```
+pub fn affirm_with_rng(
+    exit_code: i32,
+    config: &ConfigMommy,
+    rng: impl FnMut() -> u64 + 'static,
+) -> String {
+    random::with_source(Box::new(rng), || affirm_picked(exit_code, config))
+}
+
+fn affirm_picked(exit_code: i32, config: &ConfigMommy) -> String {
+    let mood = random_vec_pick(&config.moods).unwrap_or("chill");
+    let affirmations = load_mood_chain_affirmations(
+        config,
+        mood,
+        resolve_branch(exit_code, config),
+        &mut Vec::new(),
+    );
+    let style = output_style(
+        config,
+        affirmations.as_ref().and_then(AffirmationData::color),
+    );
+    select_template(exit_code, config, affirmations.as_ref(), &[], None)
+        .map(|template| {
+            styled_line(
+                &render_line(&template, config, exit_code, mood),
+                config,
+                style,
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// Run the `mommy` binary: parse the process's arguments, run the wrapped
+/// command and print an affirmation. Returns the exit code to leave with
+pub fn mommy() -> Result<i32, Box<dyn std::error::Error>> {
+    let mut config = load_config();
+    let is_cargo_command = config.binary_info.is_cargo_subcommand;
+
+    // Check recursion limit
+    if recursion_exceeded(&config) {
+        warn(
+            &config,
+            "Recursion limit exceeded! Mommy is stuck in a loop~",
+        );
+        return Ok(2); // Special exit code for recursion overflow
+    }
+
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        warn(&config, &usage_line(&config, &args[0]));
+        exit(1);
+    }
+
+    // Skip the binary name for processing
+    let mut command_args = &args[1..];
+
+    // If running as cargo subcommand, skip "cargo" if it's the first arg, or
+    // the subcommand name cargo passes along (`cargo mommy build` runs
+    // `cargo-mommy mommy build`)
+    if is_cargo_command
+        && command_args
+            .first()
+            .is_some_and(|arg| arg == "cargo" || *arg == config.binary_info.role)
+    {
+        command_args = &command_args[1..];
+    }
+
+    let (flags, command_args) = parse_leading_flags(command_args)?;
+
+    // An explicit config file replaces the default one, and has to work
+    if let Some(path) = flags.config {
+        let file = read_config_file(Path::new(path))
+            .map_err(|e| format!("Can't use the config file {e}"))?;
+        config = load_config_with(&file);
+    }
+
+    // Without AFFIRMATIONS, pick up a file from the config dir if there is one
+    if config.affirmations.is_none() {
+        config.affirmations =
+            default_affirmations_path().map(|path| path.to_string_lossy().into_owned());
+    }
+
+    // Kill switch: behave like a transparent exec, no mommy features at all.
+    // Mommy's own flags are dropped without being checked
+    if config.disable {
+        return match flags.dry_run {
+            Some(exit_code) => Ok(exit_code),
+            None => run_passthrough(&config, &passthrough_args(command_args)),
+        };
+    }
+
+    config.json_output = flags.format.map(parse_format).transpose()?.unwrap_or(false);
+    config.quiet = resolve_quiet(config.quiet, flags.quiet);
+    config.force_color = flags.color.unwrap_or_default().force();
+    config.force_branch = flags.force_branch.map(parse_force_branch).transpose()?;
+    let dry_run = flags.dry_run;
+    // A descriptor named in OUTPUT has to be open for writing
+    #[cfg(unix)]
+    if let crate::utils::Output::Fd(fd) = config.output {
+        if !config.output.is_writable() {
+            warn(
+                &config,
+                &format!("mommy can't write to fd {fd}, using stderr instead"),
+            );
+            config.output = crate::utils::Output::Stderr;
+        }
+    }
+
+    match parse_info_flag(command_args) {
+        Some(InfoFlag::Help) => {
+            println!("{}", help_text(&config, &args[0]));
+            return Ok(0);
+        }
+        Some(InfoFlag::Version) => {
+            println!("{} {}", config.binary_info.role, env!("CARGO_PKG_VERSION"));
+            return Ok(0);
+        }
+        Some(InfoFlag::ListMoods) => {
+            let mut warnings = Vec::new();
+            let moods = available_moods(
+                config.pack.as_deref(),
+                config.affirmations.as_deref(),
+                config.max_affirmations_bytes,
+                &mut warnings,
+            );
+            warn_each(&config, &warnings);
+            let moods = moods.ok_or("Failed to load any affirmations")?;
+            println!("{}", moods.join("\n"));
+            return Ok(0);
+        }
+        Some(InfoFlag::Init { force }) => {
+            for (path, written) in init_config_files(force)? {
+                if written {
+                    println!("wrote {}", path.display());
+                } else {
+                    println!("kept {}, use --init --force to replace it", path.display());
+                }
+            }
+            return Ok(0);
+        }
+        None => {}
+    }
+
+    // Check for role transformation
+    if let Some(new_role) = check_role_transformation(command_args, config.transform_anywhere) {
+        perform_role_transformation(new_role, &config.binary_info)?;
+        return Ok(0);
+    }
+
+    if command_args.first().is_some_and(|arg| arg == "sample") {
+        return run_sample(&command_args[1..], &config);
+    }
+
+    if command_args
+        .first()
+        .is_some_and(|arg| arg == "validate" || arg == "--validate")
+    {
+        let mut out = io::stdout();
+        return Ok(match command_args.get(1) {
+            Some(path) => crate::validate::run_validate_file(path, &config, &mut out)?,
+            None => crate::validate::run_validate(&config, &mut out)?,
+        });
+    }
+
+    if command_args.first().is_some_and(|arg| arg == "bench") {
+        // A closed pipe (e.g. `| head`) just means nobody wants the rest
+        let _ = run_bench(&config, BENCH_ITERATIONS, &mut io::stdout());
+        return Ok(0);
+    }
+
+    // Nothing to run, straight to the affirmation
+    if let Some(exit_code) = dry_run {
+        print_affirmation(exit_code, &config)?;
+        return Ok(map_exit_code(&config.exit_map, exit_code));
+    }
+
+    // Handle "please" for begging mode (if enabled)
+    #[cfg(feature = "beg")]
+    {
+        handle_begging(command_args, &config)?;
+        config.begging = command_args.iter().any(|arg| arg == "please");
+    }
+
+    let filtered_args = forwarded_args(command_args);
+
+    #[cfg(unix)]
+    crate::interrupt::catch();
+
+    let finished = execute_command(&config, &filtered_args)?;
+    let exit_code = finished.exit_code;
+    config.last_stderr = finished.last_stderr;
+    config.warn_count = finished.counts.warnings;
+    config.error_count = finished.counts.errors;
+    config.timed_out = finished.timed_out;
+
+    #[cfg(unix)]
+    {
+        config.interrupted = crate::interrupt::was_interrupted();
+    }
+
+    // Update begging state (if enabled)
+    #[cfg(feature = "beg")]
+    update_begging_state(exit_code, &config)?;
+
+    // Affirmations react to the real exit code, only the returned one is remapped
+    print_affirmation(exit_code, &config)?;
+
+    Ok(map_exit_code(&config.exit_map, exit_code))
+}
+
+#[cfg(test)]
+mod tests {
     use super::*;
+    use crate::config::ENV_TEST_LOCK;
+    use std::cell::RefCell;
+
+    /// In-memory stand-in for a remembered value file
+    #[derive(Default)]
+    struct MemoryTextStore(RefCell<Option<String>>);
+
+    impl TextStore for MemoryTextStore {
+        fn load(&self) -> Option<String> {
+            self.0.borrow().clone()
+        }
+
+        fn store(&self, text: &str) -> io::Result<()> {
+            *self.0.borrow_mut() = Some(text.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!(parse_format("json"), Ok(true));
+        assert_eq!(parse_format("text"), Ok(false));
+        assert!(parse_format("yaml").is_err());
+    }
+
+    #[test]
+    fn test_parse_leading_flags() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        // Expect: mommy's flags in either order
+        let json_dry_run = LeadingFlags {
+            format: Some("json"),
+            dry_run: Some(1),
+            ..LeadingFlags::default()
+        };
+        let given = args(&["--dry-run", "1", "--format=json"]);
+        let (flags, rest) = parse_leading_flags(&given).unwrap();
+        assert_eq!(flags, json_dry_run);
+        assert!(rest.is_empty());
+        let given = args(&["--format=json", "--dry-run", "1"]);
+        let (flags, rest) = parse_leading_flags(&given).unwrap();
+        assert_eq!(flags, json_dry_run);
+        assert!(rest.is_empty());
+
+        // Expect: every flag, up to the command
+        let given = args(&[
+            "--color=never",
+            "--force-branch=neutral",
+            "--config=mommy.toml",
+            "--no-quiet",
+            "make",
+            "-q",
+        ]);
+        let (flags, rest) = parse_leading_flags(&given).unwrap();
+        assert_eq!(
+            flags,
+            LeadingFlags {
+                config: Some("mommy.toml"),
+                quiet: Some(false),
+                color: Some(ColorMode::Never),
+                force_branch: Some("neutral"),
+                ..LeadingFlags::default()
+            }
+        );
+        assert_eq!(rest, &given[4..]);
+
+        // Expect: a wrapped command's own flags are left alone
+        let given = args(&["docker", "ps", "--format=json", "--dry-run", "1"]);
+        let (flags, rest) = parse_leading_flags(&given).unwrap();
+        assert_eq!(flags, LeadingFlags::default());
+        assert_eq!(rest, &given[..]);
+
+        // Expect: a broken flag is still an error
+        assert!(parse_leading_flags(&args(&["--format=json", "--dry-run"])).is_err());
+    }
+
+    #[test]
+    fn test_json_record() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.force_branch = None;
+        config.binary_info.role = "mommy".to_string();
+
+        let record = json_record(1, &config, "chill", "oh no, \"sweetie\"~").unwrap();
+        let value: serde_json::Value = serde_json::from_str(&record).unwrap();
+
+        // Expect: every key, and the text without any ANSI styling
+        assert_eq!(value["exit_code"], 1);
+        assert_eq!(value["mood"], "chill");
+        assert_eq!(value["type"], "negative");
+        assert_eq!(value["affirmation"], "oh no, \"sweetie\"~");
+        assert_eq!(value["role"], "mommy");
+        assert!(!record.contains('\x1b'));
+
+        let record = json_record(0, &config, "chill", "good~").unwrap();
+        assert!(record.contains(r#""type":"positive""#));
+
+        // Expect: NEUTRAL_CODES and a forced neutral branch report neutral
+        config.neutral_codes = vec![130];
+        let record = json_record(130, &config, "chill", "rest~").unwrap();
+        assert!(record.contains(r#""type":"neutral""#));
+        let record = json_record(1, &config, "chill", "oops~").unwrap();
+        assert!(record.contains(r#""type":"negative""#));
+        config.force_branch = Some(Branch::Neutral);
+        let record = json_record(0, &config, "chill", "rest~").unwrap();
+        assert!(record.contains(r#""type":"neutral""#));
+    }
+
+    #[test]
+    fn test_parse_dry_run() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        // Expect: flag and code are taken out together
+        let given = args(&["--dry-run", "3", "--force-branch=positive"]);
+        let (code, rest) = parse_dry_run(&given).unwrap();
+        assert_eq!(code, Some(3));
+        assert_eq!(rest, &given[2..]);
+
+        let given = args(&["--dry-run", "-1"]);
+        let (code, rest) = parse_dry_run(&given).unwrap();
+        assert_eq!(code, Some(-1));
+        assert!(rest.is_empty());
+
+        // Expect: a missing or non-numeric code is a clear error
+        let err = parse_dry_run(&args(&["--dry-run"])).unwrap_err();
+        assert!(err.contains("needs an exit code"), "{err}");
+        let err = parse_dry_run(&args(&["--dry-run", "oops"])).unwrap_err();
+        assert!(err.contains("Invalid exit code 'oops'"), "{err}");
+
+        // Expect: only the leading argument counts
+        let given = args(&["ls", "--dry-run", "1"]);
+        let (code, rest) = parse_dry_run(&given).unwrap();
+        assert_eq!(code, None);
+        assert_eq!(rest, &given[..]);
+    }
+
+    #[test]
+    fn test_parse_config_flag() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        // Expect: the path is taken out along with the flag
+        let given = args(&["--config", "/tmp/mommy.toml", "ls", "-l"]);
+        let (path, rest) = parse_config_flag(&given).unwrap();
+        assert_eq!(path, Some("/tmp/mommy.toml"));
+        assert_eq!(rest, &given[2..]);
+
+        let given = args(&["--config=/tmp/mommy.toml", "ls"]);
+        let (path, rest) = parse_config_flag(&given).unwrap();
+        assert_eq!(path, Some("/tmp/mommy.toml"));
+        assert_eq!(rest, &given[1..]);
+
+        // Expect: a missing path is an error
+        assert!(parse_config_flag(&args(&["--config"])).is_err());
+        assert!(parse_config_flag(&args(&["--config="])).is_err());
+
+        // Expect: the wrapped command's own --config is left alone
+        let given = args(&["git", "--config", "x"]);
+        let (path, rest) = parse_config_flag(&given).unwrap();
+        assert_eq!(path, None);
+        assert_eq!(rest, &given[..]);
+    }
+
+    #[test]
+    fn test_parse_info_flag() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(parse_info_flag(&args(&["--help"])), Some(InfoFlag::Help));
+        assert_eq!(parse_info_flag(&args(&["-h"])), Some(InfoFlag::Help));
+        assert_eq!(
+            parse_info_flag(&args(&["--version"])),
+            Some(InfoFlag::Version)
+        );
+        assert_eq!(
+            parse_info_flag(&args(&["-V", "ls"])),
+            Some(InfoFlag::Version)
+        );
+        assert_eq!(
+            parse_info_flag(&args(&["--list-moods"])),
+            Some(InfoFlag::ListMoods)
+        );
+        assert_eq!(
+            parse_info_flag(&args(&["--init"])),
+            Some(InfoFlag::Init { force: false })
+        );
+        assert_eq!(
+            parse_info_flag(&args(&["--init", "--force"])),
+            Some(InfoFlag::Init { force: true })
+        );
+
+        // Expect: flags belonging to the wrapped command are left alone
+        assert_eq!(parse_info_flag(&args(&["ls", "--help"])), None);
+        assert_eq!(parse_info_flag(&args(&["grep", "-h", "x"])), None);
+        assert_eq!(parse_info_flag(&args(&["cargo", "--version"])), None);
+        assert_eq!(parse_info_flag(&[]), None);
+    }
+
+    #[test]
+    fn test_help_text_uses_role() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.binary_info.role = "daddy".to_string();
+        config.binary_info.is_cargo_subcommand = false;
+        config.usage = None;
+        config.needy = false;
+
+        let help = help_text(&config, "daddy");
+        assert!(help.starts_with("Usage: daddy <command>"), "{help}");
+        assert!(help.contains("daddy runs your command"), "{help}");
+        assert!(!help.contains("mommy"), "{help}");
+    }
+
+    #[test]
+    fn test_should_speak() {
+        // Expect: the extremes never roll the dice wrong
+        assert!((0..100).all(|_| should_speak(1.0)));
+        assert!((0..100).all(|_| !should_speak(0.0)));
+
+        // Expect: a seeded roll gives the same decision every time
+        fastrand::seed(7);
+        let first: Vec<bool> = (0..20).map(|_| should_speak(0.5)).collect();
+        fastrand::seed(7);
+        let second: Vec<bool> = (0..20).map(|_| should_speak(0.5)).collect();
+        assert_eq!(first, second);
+        assert!(first.contains(&true) && first.contains(&false));
+    }
+
+    #[test]
+    fn test_parse_alias_file() {
+        let aliases = parse_alias_file(
+            "# my aliases\n\
+             ll=ls -la\n\
+             alias gs='git status'\n\
+             g=\"git\"\n\
+             mkcd() { mkdir -p \"$1\" && cd \"$1\"; }\n\
+             empty=\n",
+        );
+        let words = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        // Expect: plain and shell-style lines, quotes stripped
+        assert_eq!(aliases.get("ll"), Some(&words(&["ls", "-la"])));
+        assert_eq!(aliases.get("gs"), Some(&words(&["git", "status"])));
+        assert_eq!(aliases.get("g"), Some(&words(&["git"])));
+
+        // Expect: comments, functions and empty aliases skipped
+        assert_eq!(aliases.len(), 3);
+    }
+
+    #[test]
+    fn test_expand_alias() {
+        let aliases = parse_alias_file("ll=ls -la\ngs=git status\n");
+
+        // Expect: the first word is replaced, the rest kept
+        assert_eq!(
+            expand_alias(&["ll", "/tmp"], &aliases),
+            vec!["ls", "-la", "/tmp"]
+        );
+        assert_eq!(expand_alias(&["gs"], &aliases), vec!["git", "status"]);
+
+        // Expect: only the first word counts
+        assert_eq!(expand_alias(&["echo", "ll"], &aliases), vec!["echo", "ll"]);
+
+        // Expect: commands without an alias pass through unchanged
+        assert_eq!(expand_alias(&["ls", "-l"], &aliases), vec!["ls", "-l"]);
+        assert!(expand_alias(&[], &aliases).is_empty());
+    }
+
+    #[test]
+    fn test_pick_shell() {
+        let sh = |shell: &str| (shell.to_string(), vec!["-c".to_string()]);
+
+        // Expect: a POSIX-compatible $SHELL is used as is
+        assert_eq!(pick_shell(Some("/usr/bin/zsh"), true), sh("/usr/bin/zsh"));
+        assert_eq!(pick_shell(Some("/bin/dash"), false), sh("/bin/dash"));
+
+        // Expect: other shells fall back to bash, then sh
+        assert_eq!(pick_shell(Some("/usr/bin/fish"), true), sh("bash"));
+        assert_eq!(pick_shell(Some("/usr/bin/fish"), false), sh("sh"));
+        assert_eq!(pick_shell(None, true), sh("bash"));
+        assert_eq!(pick_shell(None, false), sh("sh"));
+        assert_eq!(pick_shell(Some(""), false), sh("sh"));
+    }
 
     #[test]
     fn test_check_role_transformation_middle() {
         let args = vec![
-            "mommy".to_string(),
+            "echo".to_string(),
             "i".to_string(),
             "mean".to_string(),
             "daddy".to_string(),
         ];
-        assert_eq!(check_role_transformation(&args), Some("daddy"));
+        // Expect: mid-command words are left alone unless scanning anywhere
+        assert_eq!(check_role_transformation(&args, false), None);
+        assert_eq!(check_role_transformation(&args, true), Some("daddy"));
     }
 
     #[test]
     fn test_check_role_transformation_start() {
         let args = vec!["i".to_string(), "mean".to_string(), "daddy".to_string()];
-        assert_eq!(check_role_transformation(&args), Some("daddy"));
+        // Expect: a leading phrase triggers in both modes
+        assert_eq!(check_role_transformation(&args, false), Some("daddy"));
+        assert_eq!(check_role_transformation(&args, true), Some("daddy"));
     }
 
     #[test]
     fn test_check_role_transformation_no_match() {
         let args = vec!["ls".to_string(), "-la".to_string()];
-        assert_eq!(check_role_transformation(&args), None);
+        assert_eq!(check_role_transformation(&args, true), None);
     }
 
     #[test]
     fn test_check_role_transformation_incomplete() {
         let args = vec!["i".to_string(), "mean".to_string()];
-        assert_eq!(check_role_transformation(&args), None);
+        assert_eq!(check_role_transformation(&args, true), None);
     }
 
     #[test]
     fn test_check_role_transformation_wrong_pattern() {
         let args = vec!["i".to_string(), "think".to_string(), "daddy".to_string()];
-        assert_eq!(check_role_transformation(&args), None);
+        assert_eq!(check_role_transformation(&args, true), None);
     }
 
     #[test]
     fn test_check_role_transformation_empty() {
         let args: Vec<String> = vec![];
-        assert_eq!(check_role_transformation(&args), None);
+        assert_eq!(check_role_transformation(&args, true), None);
     }
 
     #[test]
@@ -392,6 +2004,1062 @@ mod tests {
             "mean".to_string(),
             "mommy".to_string(),
         ];
-        assert_eq!(check_role_transformation(&args), Some("daddy"));
+        assert_eq!(check_role_transformation(&args, false), Some("daddy"));
+    }
+
+    #[test]
+    fn test_propagate_recursion_sets_env() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.recursion_depth = 3;
+        config.no_env_propagate = false;
+
+        config.chain_seed = None;
+
+        let mut cmd = Command::new("true");
+        propagate_recursion(&mut cmd, &config, "SHELL_MOMMY");
+
+        let envs: Vec<_> = cmd.get_envs().collect();
+        assert_eq!(
+            envs,
+            vec![(
+                std::ffi::OsStr::new("SHELL_MOMMY_RECURSION_LIMIT"),
+                Some(std::ffi::OsStr::new("4"))
+            )]
+        );
+    }
+
+    #[test]
+    fn test_recursion_exceeded_boundary() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.recursion_max = 5;
+
+        // Expect: depths below the max are fine, the max itself is too deep
+        config.recursion_depth = 4;
+        assert!(!recursion_exceeded(&config));
+        config.recursion_depth = 5;
+        assert!(recursion_exceeded(&config));
+        config.recursion_depth = 6;
+        assert!(recursion_exceeded(&config));
+
+        // Expect: a max of 0 refuses even the top level
+        config.recursion_max = 0;
+        config.recursion_depth = 0;
+        assert!(recursion_exceeded(&config));
+    }
+
+    #[test]
+    fn test_depth_speaks() {
+        // Expect: everyone speaks by default
+        assert!(depth_speaks(0, false));
+        assert!(depth_speaks(3, false));
+
+        // Expect: only the outermost mommy speaks with RECURSION_QUIET
+        assert!(depth_speaks(0, true));
+        assert!(!depth_speaks(1, true));
+        assert!(!depth_speaks(3, true));
+    }
+
+    #[test]
+    fn test_propagate_recursion_disabled() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.no_env_propagate = true;
+        config.chain_seed = Some(7);
+
+        let mut cmd = Command::new("true");
+        propagate_recursion(&mut cmd, &config, "CARGO_MOMMY");
+
+        // Expect: the child's environment is left untouched
+        assert_eq!(cmd.get_envs().count(), 0);
+    }
+
+    #[test]
+    fn test_exit_code_template_command_not_found() {
+        assert_eq!(exit_code_template(127, false), Some(COMMAND_NOT_FOUND));
+
+        // Expect: cargo and other codes use the regular pools
+        assert_eq!(exit_code_template(127, true), None);
+        assert_eq!(exit_code_template(1, false), None);
+        assert_eq!(exit_code_template(0, false), None);
+    }
+
+    #[test]
+    fn test_propagate_recursion_passes_chain_seed() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.recursion_depth = 0;
+        config.no_env_propagate = false;
+        config.chain_seed = Some(1234);
+
+        let mut cmd = Command::new("true");
+        propagate_recursion(&mut cmd, &config, "CARGO_MOMMY");
+
+        // Expect: the child receives the same seed to stay consistent
+        assert!(cmd.get_envs().any(|(k, v)| {
+            k == "CARGO_MOMMY_CHAIN_SEED" && v == Some(std::ffi::OsStr::new("1234"))
+        }));
+    }
+
+    #[test]
+    fn test_ci_line() {
+        assert_eq!(ci_line("mommy", 0), "mommy: success (exit 0)");
+        assert_eq!(ci_line("mommy", 1), "mommy: failure (exit 1)");
+        assert_eq!(ci_line("daddy", 101), "daddy: failure (exit 101)");
+    }
+
+    #[test]
+    fn test_sample_templates_uniform() {
+        fastrand::seed(42);
+        let pool: Vec<Affirmation> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|s| Affirmation {
+                text: s.to_string(),
+                ..Affirmation::default()
+            })
+            .collect();
+        let counts = sample_templates(&pool, 4000);
+
+        // Expect: every line comes out roughly a quarter of the time
+        assert_eq!(counts.iter().map(|(_, c)| c).sum::<usize>(), 4000);
+        for (line, count) in counts {
+            assert!(
+                (850..=1150).contains(&count),
+                "line {line} drawn {count} times"
+            );
+        }
+    }
+
+    #[test]
+    fn test_format_histogram() {
+        let histogram = format_histogram(&[("good {little}~", 10), ("line\ntwo", 5)]);
+        let rows: Vec<&str> = histogram.lines().collect();
+
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].starts_with("    10 ########################################"));
+        assert!(rows[0].ends_with("good {little}~"));
+        assert!(rows[1].contains(&"#".repeat(20)));
+        assert!(rows[1].ends_with("line two"));
+    }
+
+    #[test]
+    fn test_parse_sample_args() {
+        let args = vec![
+            "--n".to_string(),
+            "50".to_string(),
+            "--mood".to_string(),
+            "ominous".to_string(),
+        ];
+        assert_eq!(parse_sample_args(&args), Ok((50, Some("ominous"), false)));
+        assert_eq!(parse_sample_args(&[]), Ok((1000, None, false)));
+        assert!(parse_sample_args(&["--n".to_string()]).is_err());
+        assert!(parse_sample_args(&["--n".to_string(), "x".to_string()]).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_command_capture() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.needy = false;
+        config.aliases = None;
+        config.no_env_propagate = true;
+        config.binary_info.is_cargo_subcommand = false;
+        config.capture = true;
+
+        // Expect: output passes through and the exit code is kept
+        let finished = execute_command(&config, &["echo", "hello"]).unwrap();
+        assert_eq!(finished.exit_code, 0);
+        assert_eq!(finished.last_stderr, None);
+
+        // Expect: the last non-empty stderr line is captured
+        let script = "echo first >&2; echo 'error: it broke' >&2; echo >&2; exit 2";
+        let finished = execute_command(&config, &["sh", "-c", script]).unwrap();
+        assert_eq!(finished.exit_code, 2);
+        assert_eq!(finished.last_stderr.as_deref(), Some("error: it broke"));
+        assert_eq!(
+            finished.counts,
+            DiagnosticCounts {
+                warnings: 0,
+                errors: 1
+            }
+        );
+
+        // Expect: nothing is captured with the default inherited stdio
+        config.capture = false;
+        let finished = execute_command(&config, &["sh", "-c", "echo error: >&2; exit 1"]).unwrap();
+        assert_eq!(finished.exit_code, 1);
+        assert_eq!(finished.last_stderr, None);
+        assert_eq!(finished.counts, DiagnosticCounts::default());
+    }
+
+    #[test]
+    fn test_diagnostic_counts() {
+        let count = |output: &str| {
+            let mut counts = DiagnosticCounts::default();
+            output.lines().for_each(|line| counts.add_line(line));
+            counts
+        };
+        let cargo_output = r#"   Compiling mommy v0.1.6 (/src/mommy)
+warning: unused variable: `x`
+ --> src/main.rs:2:9
+  |
+2 |     let x = 1;
+  |         ^ help: if this is intentional, prefix it with an underscore: `_x`
+  |
+  = note: `#[warn(unused_variables)]` on by default
+
+warning[E0170]: pattern binding `Red` is named the same as one of the variants
+error[E0308]: mismatched types
+ --> src/main.rs:4:18
+error: cannot find value `y` in this scope
+warning: `mommy` (bin "mommy") generated 2 warnings
+error: could not compile `mommy` (bin "mommy") due to 2 previous errors; 2 warnings emitted
+"#;
+
+        // Expect: each diagnostic once, summaries and notes left out
+        assert_eq!(
+            count(cargo_output),
+            DiagnosticCounts {
+                warnings: 2,
+                errors: 2
+            }
+        );
+
+        // Expect: the same with cargo's colors on
+        let colored = "\x1b[0m\x1b[1m\x1b[33mwarning\x1b[0m\x1b[0m\x1b[1m: unused import\x1b[0m\n\
+                       \x1b[0m\x1b[1m\x1b[38;5;9merror[E0425]\x1b[0m\x1b[0m\x1b[1m: oops\x1b[0m";
+        assert_eq!(
+            count(colored),
+            DiagnosticCounts {
+                warnings: 1,
+                errors: 1
+            }
+        );
+
+        // Expect: words in the middle of a line don't count
+        assert_eq!(
+            count("no error: here\nsome warnings: none\nerrors: 0"),
+            DiagnosticCounts::default()
+        );
+        assert_eq!(strip_ansi("\x1b[1;31mred\x1b[0m plain"), "red plain");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_execute_command_timeout() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.needy = false;
+        config.aliases = None;
+        config.no_env_propagate = true;
+        config.binary_info.is_cargo_subcommand = false;
+        config.capture = false;
+        config.timeout = Some(Duration::from_secs(1));
+
+        // Expect: a command running past the limit is killed and reports 124
+        let start = Instant::now();
+        let finished = execute_command(&config, &["sleep", "5"]).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(4));
+        assert_eq!(finished.exit_code, TIMEOUT_EXIT_CODE);
+        assert!(finished.timed_out);
+
+        // Expect: a fast command finishes as usual
+        let finished = execute_command(&config, &["sh", "-c", "exit 3"]).unwrap();
+        assert_eq!(finished.exit_code, 3);
+        assert!(!finished.timed_out);
+
+        // Expect: the same with captured output
+        config.capture = true;
+        let finished = execute_command(&config, &["sleep", "5"]).unwrap();
+        assert_eq!(finished.exit_code, TIMEOUT_EXIT_CODE);
+        assert!(finished.timed_out);
+
+        // Expect: a grandchild still holding the pipes doesn't keep mommy
+        // waiting after the kill
+        let start = Instant::now();
+        let finished = execute_command(&config, &["sh", "-c", "sleep 30 & wait"]).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(4));
+        assert_eq!(finished.exit_code, TIMEOUT_EXIT_CODE);
+        assert!(finished.timed_out);
+    }
+
+    #[test]
+    fn test_select_template_stderr_error() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.only_negative = false;
+        config.only_positive = false;
+        config.force_branch = None;
+        config.interrupted = false;
+        config.last_stderr = Some("Error: file not found".to_string());
+        let affirmations = load_affirmations_with_mood_mixing("chill", false).unwrap();
+
+        // Expect: a failure with an error on stderr gets the contextual line
+        let template = select_template(1, &config, Some(&affirmations), &[], None).unwrap();
+        assert_eq!(template, STDERR_ERROR);
+
+        // Expect: success and unrelated stderr lines use the regular pools
+        let template = select_template(0, &config, Some(&affirmations), &[], None).unwrap();
+        assert!(affirmations.positive().iter().any(|a| a.text == template));
+        config.last_stderr = Some("warning: unused".to_string());
+        let template = select_template(1, &config, Some(&affirmations), &[], None).unwrap();
+        assert!(affirmations.negative().iter().any(|a| a.text == template));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_passthrough_only_runs_command() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.needy = false;
+        config.disable = true;
+        config.binary_info.is_cargo_subcommand = false;
+
+        assert_eq!(
+            run_passthrough(&config, &["sh", "-c", "exit 3"]).unwrap(),
+            3
+        );
+
+        // Expect: "please" is an ordinary argument when mommy is disabled
+        let args = ["test", "please", "=", "please"];
+        assert_eq!(run_passthrough(&config, &args).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_passthrough_args() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let passthrough = |given: &[String]| -> Vec<String> {
+            let (_, rest) = parse_leading_flags(given).unwrap();
+            passthrough_args(rest)
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        };
+
+        // Expect: mommy's own flags are dropped before the command runs
+        let given = args(&["-q", "sh", "-c", "exit 3"]);
+        assert_eq!(passthrough(&given), vec!["sh", "-c", "exit 3"]);
+        let given = args(&["--no-quiet", "--color=never", "--force-branch=up", "make"]);
+        assert_eq!(passthrough(&given), vec!["make"]);
+        let given = args(&["make", "--force-branch=up"]);
+        assert_eq!(passthrough(&given), vec!["make", "--force-branch=up"]);
+
+        // Expect: "please" and the wrapped command's own -q stay
+        let given = args(&["grep", "-q", "please", "file"]);
+        assert_eq!(passthrough(&given), vec!["grep", "-q", "please", "file"]);
+
+        // Expect: DISABLE=1 with `-q <cmd>` runs the command
+        let mut config = load_config();
+        config.needy = false;
+        config.disable = true;
+        config.binary_info.is_cargo_subcommand = false;
+        let given = args(&["-q", "sh", "-c", "exit 3"]);
+        let (_, rest) = parse_leading_flags(&given).unwrap();
+        assert_eq!(
+            run_passthrough(&config, &passthrough_args(rest)).unwrap(),
+            3
+        );
+
+        // Expect: --format and --force-branch aren't checked while parsing
+        let given = args(&["--format=yaml", "--force-branch=up", "ls"]);
+        let (flags, rest) = parse_leading_flags(&given).unwrap();
+        assert_eq!(flags.format, Some("yaml"));
+        assert_eq!(flags.force_branch, Some("up"));
+        assert_eq!(passthrough_args(rest), vec!["ls"]);
+    }
+
+    #[test]
+    fn test_run_passthrough_needy() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.needy = true;
+
+        assert_eq!(run_passthrough(&config, &["42"]).unwrap(), 42);
+        assert!(run_passthrough(&config, &["nope"]).is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sigkill_maps_to_out_of_memory() {
+        use std::os::unix::process::ExitStatusExt;
+
+        // Raw wait status 9: terminated by signal 9 (SIGKILL)
+        let status = ExitStatus::from_raw(9);
+        assert_eq!(exit_code_from_status(status), 137);
+        assert_eq!(exit_code_template(137, false), Some(OUT_OF_MEMORY));
+        assert_eq!(exit_code_template(137, true), Some(OUT_OF_MEMORY));
+
+        // Expect: regular exits keep their own code
+        let status = ExitStatus::from_raw(3 << 8);
+        assert_eq!(exit_code_from_status(status), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signal_maps_to_shell_exit_code() {
+        use std::os::unix::process::ExitStatusExt;
+
+        // Expect: 128 + signal for SIGINT, SIGSEGV and SIGTERM
+        assert_eq!(exit_code_from_status(ExitStatus::from_raw(2)), 130);
+        assert_eq!(exit_code_from_status(ExitStatus::from_raw(11)), 139);
+        assert_eq!(exit_code_from_status(ExitStatus::from_raw(15)), 143);
+        assert_eq!(
+            Branch::for_exit_code(exit_code_from_status(ExitStatus::from_raw(15))),
+            Branch::Negative
+        );
+    }
+
+    #[test]
+    fn test_usage_line_default() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.usage = None;
+        config.needy = false;
+        config.binary_info.is_cargo_subcommand = false;
+        assert_eq!(
+            usage_line(&config, "mommy"),
+            "Usage: mommy <command> [args ...]"
+        );
+
+        config.needy = true;
+        assert_eq!(usage_line(&config, "mommy"), "Usage: mommy <exit_code>");
+
+        config.binary_info.is_cargo_subcommand = true;
+        config.binary_info.role = "daddy".to_string();
+        assert_eq!(
+            usage_line(&config, "cargo-daddy"),
+            "Usage: cargo daddy <cargo-command> [args...]"
+        );
+    }
+
+    #[test]
+    fn test_usage_line_custom_template() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.binary_info.is_cargo_subcommand = false;
+        config.roles = vec!["mommy".to_string()];
+        config.little = vec!["kitten".to_string()];
+        config.usage = Some("{roles} wants {little} to run: {bin} <befehl>".to_string());
+
+        assert_eq!(
+            usage_line(&config, "/usr/bin/mommy"),
+            "mommy wants kitten to run: /usr/bin/mommy <befehl>"
+        );
+    }
+
+    fn no_rotation() -> MemoryTextStore {
+        MemoryTextStore(RefCell::new(None))
+    }
+
+    #[test]
+    fn test_next_mood_index() {
+        // Expect: advances and wraps around
+        assert_eq!(next_mood_index(Some("0"), 3), 1);
+        assert_eq!(next_mood_index(Some("1"), 3), 2);
+        assert_eq!(next_mood_index(Some("2"), 3), 0);
+        assert_eq!(next_mood_index(Some(" 1\n"), 3), 2);
+
+        // Expect: missing or corrupt state starts at 0
+        assert_eq!(next_mood_index(None, 3), 0);
+        assert_eq!(next_mood_index(Some("banana"), 3), 0);
+        assert_eq!(next_mood_index(Some("-1"), 3), 0);
+
+        // Expect: a shrunken mood list still lands in range
+        assert_eq!(next_mood_index(Some("7"), 2), 0);
+        assert_eq!(next_mood_index(Some("0"), 0), 0);
+    }
+
+    #[test]
+    fn test_select_mood_rotates() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.moods = ["chill", "ominous", "thirsty"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        config.remember_mood = false;
+        config.mood_rotate = true;
+        let store = no_rotation();
+        let rotation = no_rotation();
+
+        // Expect: moods in order, wrapping around, with the index remembered
+        let picked: Vec<String> = (0..4)
+            .map(|_| select_mood(&config, &store, &rotation))
+            .collect();
+        assert_eq!(picked, ["chill", "ominous", "thirsty", "chill"]);
+        assert_eq!(rotation.load().as_deref(), Some("0"));
+    }
+
+    #[test]
+    fn test_select_mood_reuses_remembered_mood() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.moods = vec!["chill".to_string()];
+        config.moods_explicit = false;
+        config.remember_mood = true;
+        let store = MemoryTextStore(RefCell::new(Some("ominous".to_string())));
+
+        // Expect: the remembered mood wins over the default
+        assert_eq!(select_mood(&config, &store, &no_rotation()), "ominous");
+    }
+
+    #[test]
+    fn test_select_mood_updates_remembered_mood() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.moods = vec!["thirsty".to_string()];
+        config.moods_explicit = true;
+        config.remember_mood = true;
+        let store = MemoryTextStore(RefCell::new(Some("ominous".to_string())));
+
+        // Expect: an explicit mood is used and remembered for next time
+        assert_eq!(select_mood(&config, &store, &no_rotation()), "thirsty");
+        assert_eq!(store.load().as_deref(), Some("thirsty"));
+
+        config.moods_explicit = false;
+        config.moods = vec!["chill".to_string()];
+        assert_eq!(select_mood(&config, &store, &no_rotation()), "thirsty");
+    }
+
+    #[test]
+    fn test_select_mood_without_memory() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.moods = vec!["chill".to_string()];
+        config.moods_explicit = false;
+        config.remember_mood = false;
+        let store = MemoryTextStore(RefCell::new(Some("ominous".to_string())));
+
+        // Expect: the store is neither used nor touched
+        assert_eq!(select_mood(&config, &store, &no_rotation()), "chill");
+        assert_eq!(store.load().as_deref(), Some("ominous"));
+    }
+
+    #[test]
+    fn test_map_exit_code() {
+        let exit_map = HashMap::from([(101, 1), (2, 0)]);
+
+        assert_eq!(map_exit_code(&exit_map, 101), 1);
+        assert_eq!(map_exit_code(&exit_map, 2), 0);
+
+        // Expect: unmapped codes and an empty map are the identity
+        assert_eq!(map_exit_code(&exit_map, 3), 3);
+        assert_eq!(map_exit_code(&HashMap::new(), 101), 101);
+    }
+
+    #[test]
+    fn test_strip_quiet_flag() {
+        let args = |list: &[&str]| -> Vec<String> { list.iter().map(|s| s.to_string()).collect() };
+
+        // Expect: a leading -q is mommy's and gets stripped
+        let leading = args(&["-q", "cargo", "test"]);
+        assert_eq!(strip_quiet_flag(&leading), (Some(true), &leading[1..]));
+        let leading = args(&["--quiet", "ls"]);
+        assert_eq!(strip_quiet_flag(&leading), (Some(true), &leading[1..]));
+
+        // Expect: a leading --no-quiet is stripped too
+        let leading = args(&["--no-quiet", "ls"]);
+        assert_eq!(strip_quiet_flag(&leading), (Some(false), &leading[1..]));
+
+        // Expect: anywhere else it belongs to the wrapped command
+        let trailing = args(&["grep", "-q", "foo"]);
+        assert_eq!(strip_quiet_flag(&trailing), (None, &trailing[..]));
+        let trailing = args(&["ls", "--quiet"]);
+        assert_eq!(strip_quiet_flag(&trailing), (None, &trailing[..]));
+
+        // Expect: -q before --dry-run is a quiet dry run, not a command
+        // named --dry-run
+        for given in [
+            args(&["-q", "--dry-run", "1"]),
+            args(&["--dry-run", "1", "-q"]),
+        ] {
+            let (flags, rest) = parse_leading_flags(&given).unwrap();
+            assert_eq!((flags.quiet, flags.dry_run), (Some(true), Some(1)));
+            assert!(rest.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_resolve_quiet() {
+        // Expect: without a flag, QUIET decides
+        assert!(!resolve_quiet(false, None));
+        assert!(resolve_quiet(true, None));
+
+        // Expect: a flag overrides QUIET either way
+        assert!(resolve_quiet(false, Some(true)));
+        assert!(resolve_quiet(true, Some(true)));
+        assert!(!resolve_quiet(true, Some(false)));
+        assert!(!resolve_quiet(false, Some(false)));
+    }
+
+    #[test]
+    fn test_choose_template_no_repeat() {
+        fastrand::seed(3);
+        let line = |text: &str| Affirmation {
+            text: text.to_string(),
+            ..Affirmation::default()
+        };
+        let pool = [line("one"), line("two"), line("three")];
+        let candidates: Vec<&Affirmation> = pool.iter().collect();
+
+        // Expect: two consecutive picks are never the same line
+        let mut last = choose_template_no_repeat(&candidates, "", None);
+        for _ in 0..200 {
+            let picked = choose_template_no_repeat(&candidates, "", Some(last));
+            assert_ne!(picked, last);
+            last = picked;
+        }
+
+        // Expect: a single line pool still returns its only line
+        let single = [line("only")];
+        let candidates: Vec<&Affirmation> = single.iter().collect();
+        assert_eq!(
+            choose_template_no_repeat(&candidates, "", Some("only")),
+            "only"
+        );
+    }
+
+    #[test]
+    fn test_choose_weighted() {
+        fastrand::seed(7);
+        let line = |text: &str, weight| Affirmation {
+            text: text.to_string(),
+            weight,
+            ..Affirmation::default()
+        };
+        let pool = [line("heavy", 9), line("light", 1), line("unset", 0)];
+        let candidates: Vec<&Affirmation> = pool.iter().collect();
+
+        let mut counts = [0; 3];
+        for _ in 0..11_000 {
+            let picked = choose_weighted(&candidates);
+            let idx = pool.iter().position(|a| a.text == picked).unwrap();
+            counts[idx] += 1;
+        }
+
+        // Expect: roughly 9:1:1, with a zero weight counting as 1
+        assert!((8_000..10_000).contains(&counts[0]), "{counts:?}");
+        assert!((700..1_300).contains(&counts[1]), "{counts:?}");
+        assert!((700..1_300).contains(&counts[2]), "{counts:?}");
+    }
+
+    #[test]
+    fn test_parse_color_mode() {
+        let args = |list: &[&str]| -> Vec<String> { list.iter().map(|s| s.to_string()).collect() };
+
+        for (flag, mode) in [
+            ("--color=always", ColorMode::Always),
+            ("--color=never", ColorMode::Never),
+            ("--no-color", ColorMode::Never),
+            ("--color=auto", ColorMode::Auto),
+        ] {
+            let given = args(&[flag, "ls"]);
+            assert_eq!(parse_color_mode(&given), (Some(mode), &given[1..]));
+        }
+
+        // Expect: a wrapped command's own --color is forwarded unchanged
+        let given = args(&["ls", "--color=auto"]);
+        assert_eq!(parse_color_mode(&given), (None, &given[..]));
+        assert_eq!(forwarded_args(&given), vec!["ls", "--color=auto"]);
+
+        // Expect: unknown spellings aren't mommy's
+        let given = args(&["--color=rainbow"]);
+        assert_eq!(parse_color_mode(&given), (None, &given[..]));
+
+        // Expect: each mode maps to the matching colorize override
+        assert_eq!(ColorMode::Always.force(), Some(true));
+        assert_eq!(ColorMode::Never.force(), Some(false));
+        assert_eq!(ColorMode::Auto.force(), None);
+    }
+
+    #[test]
+    fn test_parse_force_branch() {
+        assert_eq!(parse_force_branch("negative"), Ok(Branch::Negative));
+        assert_eq!(parse_force_branch("positive"), Ok(Branch::Positive));
+        assert_eq!(parse_force_branch("neutral"), Ok(Branch::Neutral));
+        assert!(parse_force_branch("sideways").is_err());
+
+        // Expect: after the command it's the command's, whatever the value
+        let args: Vec<String> = ["echo", "a", "--force-branch=up", "c"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let (flags, rest) = parse_leading_flags(&args).unwrap();
+        assert_eq!(flags.force_branch, None);
+        assert_eq!(
+            forwarded_args(rest),
+            vec!["echo", "a", "--force-branch=up", "c"]
+        );
+    }
+
+    #[test]
+    fn test_select_template_forced_negative() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.only_negative = false;
+        config.combo = false;
+        config.personas.clear();
+        config.force_branch = Some(Branch::Negative);
+        let affirmations = load_affirmations_with_mood_mixing("chill", false).unwrap();
+
+        // Expect: a negative line even though the command succeeded
+        for _ in 0..20 {
+            let template = select_template(0, &config, Some(&affirmations), &[], None).unwrap();
+            assert!(affirmations.negative().iter().any(|a| a.text == template));
+        }
+
+        // Expect: forcing positive skips the exit code specific lines
+        config.force_branch = Some(Branch::Positive);
+        config.binary_info.is_cargo_subcommand = false;
+        let template = select_template(127, &config, Some(&affirmations), &[], None).unwrap();
+        assert!(affirmations.positive().iter().any(|a| a.text == template));
+    }
+
+    #[test]
+    fn test_select_template_follows_exit_code() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.only_negative = false;
+        config.combo = false;
+        config.personas.clear();
+        config.force_branch = None;
+        let affirmations = load_affirmations_with_mood_mixing("chill", false).unwrap();
+
+        let template = select_template(0, &config, Some(&affirmations), &[], None).unwrap();
+        assert!(affirmations.positive().iter().any(|a| a.text == template));
+        let template = select_template(1, &config, Some(&affirmations), &[], None).unwrap();
+        assert!(affirmations.negative().iter().any(|a| a.text == template));
+
+        // Expect: silence on success with only_negative
+        config.only_negative = true;
+        assert_eq!(
+            select_template(0, &config, Some(&affirmations), &[], None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_select_template_only_filters() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        crate::config::use_empty_config_dir();
+        let affirmations = load_affirmations_with_mood_mixing("chill", false).unwrap();
+        // ENV_TEST_LOCK keeps other tests from reading the env meanwhile
+        let set = |key: &str, on: bool| unsafe {
+            if on {
+                env::set_var(key, "1");
+            } else {
+                env::remove_var(key);
+            }
+        };
+
+        // (ONLY_NEGATIVE set, ONLY_POSITIVE set, resolved (only_negative,
+        // only_positive), speaks on success, speaks on failure)
+        let matrix = [
+            (false, false, (false, false), true, true),
+            (true, false, (true, false), false, true),
+            (false, true, (false, true), true, false),
+            // Expect: ONLY_NEGATIVE takes precedence when both are set
+            (true, true, (true, false), false, true),
+        ];
+        for (negative_set, positive_set, resolved, on_success, on_failure) in matrix {
+            set("SHELL_MOMMY_ONLY_NEGATIVE", negative_set);
+            set("SHELL_MOMMYS_ONLY_POSITIVE", positive_set);
+            let mut config = load_config();
+            assert_eq!((config.only_negative, config.only_positive), resolved);
+
+            config.combo = false;
+            config.personas.clear();
+            config.force_branch = None;
+            let success = select_template(0, &config, Some(&affirmations), &[], None);
+            let failure = select_template(1, &config, Some(&affirmations), &[], None);
+            assert_eq!(success.is_some(), on_success);
+            assert_eq!(failure.is_some(), on_failure);
+        }
+        set("SHELL_MOMMY_ONLY_NEGATIVE", false);
+        set("SHELL_MOMMYS_ONLY_POSITIVE", false);
+    }
+
+    #[test]
+    fn test_select_template_by_code() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let path = env::temp_dir().join(format!("mommy-test-by-code-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"moods": {"chill": {
+                "positive": ["good~"],
+                "negative": ["oops~"],
+                "by_code": {"130": ["rest a bit~"], "0": ["exactly zero~"]}
+            }}}"#,
+        )
+        .unwrap();
+        let affirmations = load_custom_affirmations_with_mood_mixing(
+            &path,
+            "chill",
+            false,
+            u64::MAX,
+            &mut Vec::new(),
+        )
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut config = load_config();
+        config.only_negative = false;
+        config.only_positive = false;
+        config.combo = false;
+        config.personas.clear();
+        config.force_branch = None;
+        config.interrupted = false;
+        config.last_stderr = None;
+        config.begging = false;
+
+        // Expect: the bucket for the exact code wins over the generic pools
+        let pick = |code| select_template(code, &config, Some(&affirmations), &[], None);
+        assert_eq!(pick(130).as_deref(), Some("rest a bit~"));
+        assert_eq!(pick(0).as_deref(), Some("exactly zero~"));
+
+        // Expect: codes without a bucket use the generic pools
+        assert_eq!(pick(1).as_deref(), Some("oops~"));
+
+        // Expect: a forced branch skips the buckets
+        config.force_branch = Some(Branch::Negative);
+        let pick = |code| select_template(code, &config, Some(&affirmations), &[], None);
+        assert_eq!(pick(130).as_deref(), Some("oops~"));
+    }
+
+    #[test]
+    fn test_select_template_neutral() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let load = |json: &str| {
+            let path = env::temp_dir().join(format!(
+                "mommy-test-neutral-{}-{}.json",
+                std::process::id(),
+                json.len()
+            ));
+            std::fs::write(&path, json).unwrap();
+            let affirmations = load_custom_affirmations_with_mood_mixing(
+                &path,
+                "chill",
+                false,
+                u64::MAX,
+                &mut Vec::new(),
+            )
+            .unwrap();
+            std::fs::remove_file(&path).unwrap();
+            affirmations
+        };
+        let with_neutral = load(
+            r#"{"moods": {"chill": {
+                "positive": ["good~"],
+                "negative": ["oops~"],
+                "neutral": ["take a break~"]
+            }}}"#,
+        );
+        let without_neutral =
+            load(r#"{"moods": {"chill": {"positive": ["good~"], "negative": ["oops~"]}}}"#);
+
+        let mut config = load_config();
+        config.only_negative = false;
+        config.only_positive = false;
+        config.personas.clear();
+        config.force_branch = None;
+        config.interrupted = false;
+        config.last_stderr = None;
+        config.begging = false;
+        config.severity_match = false;
+        config.exit_map.clear();
+        config.neutral_codes = vec![130, 143];
+
+        // Expect: a neutral code picks the neutral pool when the mood has one
+        let pick =
+            |code, affirmations| select_template(code, &config, Some(affirmations), &[], None);
+        assert_eq!(pick(130, &with_neutral).as_deref(), Some("take a break~"));
+        assert_eq!(pick(143, &with_neutral).as_deref(), Some("take a break~"));
+
+        // Expect: other failures stay negative
+        assert_eq!(pick(1, &with_neutral).as_deref(), Some("oops~"));
+
+        // Expect: negative lines when the mood has no neutral ones
+        assert_eq!(pick(130, &without_neutral).as_deref(), Some("oops~"));
+
+        // Expect: codes not in NEUTRAL_CODES stay negative
+        config.neutral_codes.clear();
+        let pick =
+            |code, affirmations| select_template(code, &config, Some(affirmations), &[], None);
+        assert_eq!(pick(130, &with_neutral).as_deref(), Some("oops~"));
+
+        // Expect: a forced neutral branch uses the neutral lines for any code,
+        // or the negative ones without them
+        config.force_branch = Some(Branch::Neutral);
+        let pick =
+            |code, affirmations| select_template(code, &config, Some(affirmations), &[], None);
+        assert_eq!(pick(0, &with_neutral).as_deref(), Some("take a break~"));
+        assert_eq!(pick(0, &without_neutral).as_deref(), Some("oops~"));
+
+        // Expect: ONLY_POSITIVE silences the neutral branch like the negative one
+        config.only_positive = true;
+        let pick =
+            |code, affirmations| select_template(code, &config, Some(affirmations), &[], None);
+        assert_eq!(pick(0, &with_neutral), None);
+    }
+
+    #[cfg(feature = "beg")]
+    #[test]
+    fn test_select_template_begging() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.only_negative = false;
+        config.only_positive = false;
+        config.force_branch = None;
+        config.interrupted = false;
+        config.last_stderr = None;
+        config.begging = true;
+        let affirmations = load_affirmations_with_mood_mixing("chill", false).unwrap();
+        assert!(!affirmations.begging().is_empty());
+
+        // Expect: the begging pool on success and on failure
+        for exit_code in [0, 1] {
+            let template =
+                select_template(exit_code, &config, Some(&affirmations), &[], None).unwrap();
+            assert!(affirmations.begging().iter().any(|a| a.text == template));
+        }
+
+        // Expect: without "please", the regular pools
+        config.begging = false;
+        let template = select_template(1, &config, Some(&affirmations), &[], None).unwrap();
+        assert!(affirmations.negative().iter().any(|a| a.text == template));
+    }
+
+    #[cfg(feature = "beg")]
+    #[test]
+    fn test_forwarded_args_strip_please() {
+        let args: Vec<String> = ["please", "git", "push", "--color=never", "please"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        // Expect: "please" doesn't reach the command, git's own --color does
+        assert_eq!(forwarded_args(&args), vec!["git", "push", "--color=never"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_select_template_interrupted() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.only_negative = false;
+        config.force_branch = None;
+        config.interrupted = true;
+        let affirmations = load_affirmations_with_mood_mixing("chill", false).unwrap();
+
+        // Expect: Ctrl-C gets the gentle break line instead of a failure line
+        let template = select_template(130, &config, Some(&affirmations), &[], None).unwrap();
+        assert_eq!(template, INTERRUPTED);
+
+        // Expect: a timed out command gets its own line
+        config.interrupted = false;
+        config.timed_out = true;
+        let template = select_template(124, &config, Some(&affirmations), &[], None).unwrap();
+        assert_eq!(template, TIMED_OUT);
+        config.timed_out = false;
+
+        // Expect: without the flag, the regular negative pool
+        let template = select_template(130, &config, Some(&affirmations), &[], None).unwrap();
+        assert!(affirmations.negative().iter().any(|a| a.text == template));
+    }
+
+    #[test]
+    fn test_showcase_cycles_through_pool() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.only_negative = false;
+        config.combo = false;
+        config.personas.clear();
+        config.shell = None;
+        config.force_branch = Some(Branch::Positive);
+        let affirmations = load_affirmations_with_mood_mixing("chill", false).unwrap();
+        let pool_len = affirmations.positive().len();
+        let mut showcase = Showcase::default();
+
+        // Expect: no line repeats until the whole pool was shown
+        for _ in 0..pool_len {
+            let template =
+                select_template(0, &config, Some(&affirmations), &showcase.seen, None).unwrap();
+            assert!(!showcase.seen.contains(&template));
+            record_showcase(&mut showcase, &template);
+        }
+        assert_eq!(showcase.seen.len(), pool_len);
+
+        // Expect: the next line starts a new cycle
+        let template =
+            select_template(0, &config, Some(&affirmations), &showcase.seen, None).unwrap();
+        record_showcase(&mut showcase, &template);
+        assert_eq!(showcase.seen, vec![template]);
+        assert_eq!(showcase.runs, pool_len as u32 + 1);
+    }
+
+    #[test]
+    fn test_run_bench() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.affirmations = None;
+        let mut out = Vec::new();
+        run_bench(&config, 3, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        // Expect: one line per step, each ending in a timing
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            let (label, timing) = line.split_once(':').unwrap();
+            assert!(!label.is_empty());
+            let micros: f64 = timing.trim().trim_end_matches("µs").trim().parse().unwrap();
+            assert!(micros >= 0.0);
+        }
+    }
+
+    #[test]
+    fn test_load_mood_chain_affirmations() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let test_json = r#"{
+            "moods": {
+                "thirsty": {"positive": [], "negative": ["thirsty negative"]},
+                "ominous": {"positive": ["ominous positive"], "negative": []},
+                "chill": {"positive": ["chill positive"], "negative": ["chill negative"]}
+            }
+        }"#;
+        let path = env::temp_dir().join(format!("mommy-test-fallback-{}.json", std::process::id()));
+        std::fs::write(&path, test_json).unwrap();
+
+        let mut config = load_config();
+        config.affirmations = Some(path.display().to_string());
+        config.pack = None;
+        config.mood_mixing = false;
+        config.mood_fallback = vec!["missing".to_string(), "ominous".to_string()];
+
+        // Expect: an empty pool moves down the chain, skipping unknown moods
+        let loaded =
+            load_mood_chain_affirmations(&config, "thirsty", Branch::Positive, &mut Vec::new())
+                .unwrap();
+        assert_eq!(loaded.mood(), Some("ominous"));
+        assert_eq!(loaded.positive()[0].text, "ominous positive");
+
+        // Expect: the picked mood is kept when its pool has lines
+        let loaded =
+            load_mood_chain_affirmations(&config, "thirsty", Branch::Negative, &mut Vec::new())
+                .unwrap();
+        assert_eq!(loaded.mood(), Some("thirsty"));
+
+        // Expect: with nothing in the chain, the usual loading applies
+        config.mood_fallback = vec!["missing".to_string()];
+        let loaded =
+            load_mood_chain_affirmations(&config, "thirsty", Branch::Positive, &mut Vec::new())
+                .unwrap();
+        assert_eq!(loaded.mood(), Some("thirsty"));
+        assert!(loaded.positive().is_empty());
+
+        std::fs::remove_file(&path).unwrap();
     }
 }
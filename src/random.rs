@@ -0,0 +1,90 @@
+//! Where mommy's random picks come from: fastrand's thread-local generator,
+//! unless a host embedding the library brings its own source through
+//! `affirm_with_rng` for the duration of a call
+use std::cell::RefCell;
+use std::ops::RangeTo;
+
+/// A host-provided source of random numbers
+pub type Source = Box<dyn FnMut() -> u64>;
+
+thread_local! {
+    static SOURCE: RefCell<Option<Source>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with every pick drawn from `source`, then go back to the source
+/// used before
+pub fn with_source<R>(source: Source, f: impl FnOnce() -> R) -> R {
+    let previous = SOURCE.with(|s| s.borrow_mut().replace(source));
+    let result = f();
+    SOURCE.with(|s| *s.borrow_mut() = previous);
+    result
+}
+
+/// The next number from the host's source, `None` if there is none
+fn next() -> Option<u64> {
+    SOURCE.with(|s| s.borrow_mut().as_mut().map(|source| source()))
+}
+
+/// A number in `..range.end`, which must not be empty
+pub fn usize(range: RangeTo<usize>) -> usize {
+    match next() {
+        // u64 holds any usize mommy runs on, so the remainder fits back
+        Some(n) => (n % range.end as u64) as usize,
+        None => fastrand::usize(range),
+    }
+}
+
+/// A number in `..range.end`, which must not be empty
+pub fn u64(range: RangeTo<u64>) -> u64 {
+    match next() {
+        Some(n) => n % range.end,
+        None => fastrand::u64(range),
+    }
+}
+
+/// A number in `0.0..1.0`
+pub fn f32() -> f32 {
+    match next() {
+        // The top 24 bits are as many as an f32 holds exactly
+        Some(n) => (n >> 40) as f32 / (1u64 << 24) as f32,
+        None => fastrand::f32(),
+    }
+}
+
+/// A coin flip
+pub fn bool() -> bool {
+    match next() {
+        Some(n) => n & 1 == 1,
+        None => fastrand::bool(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter_source() {
+        let mut n = 0;
+        let counter = Box::new(move || {
+            n += 1;
+            n - 1
+        });
+
+        // Expect: picks follow the counter instead of fastrand
+        let picks = with_source(counter, || [usize(..3), usize(..3), usize(..3), usize(..3)]);
+        assert_eq!(picks, [0, 1, 2, 0]);
+        let bits = with_source(Box::new(|| 7), || (u64(..5), bool(), f32()));
+        assert_eq!(bits, (2, true, 0.0));
+        assert_eq!(
+            with_source(Box::new(|| u64::MAX), f32),
+            1.0 - 1.0 / (1u64 << 24) as f32
+        );
+
+        // Expect: afterwards fastrand is back, seeded as usual
+        fastrand::seed(42);
+        let expected = fastrand::usize(..1000);
+        fastrand::seed(42);
+        assert_eq!(usize(..1000), expected);
+    }
+}
@@ -1,8 +1,213 @@
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "beg")]
 use std::error::Error;
 use std::fs;
+use std::io;
 use std::path::PathBuf;
 
+/// Home directory, falling back to the OS temp dir.
+/// HOME is unset by default on native Windows (only USERPROFILE is guaranteed)
+fn home_dir() -> PathBuf {
+    let home = std::env::var("HOME")
+        .or_else(|_| std::env::var("USERPROFILE"))
+        .unwrap_or_else(|_| std::env::temp_dir().display().to_string());
+    PathBuf::from(home)
+}
+
+/// Directory for mommy's opt-in state files: `$XDG_STATE_HOME/mommy`, or
+/// `~/.local/state/mommy`
+pub fn state_dir() -> PathBuf {
+    std::env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(|| home_dir().join(".local/state"), PathBuf::from)
+        .join("mommy")
+}
+
+/// Directory for mommy's config file: `$XDG_CONFIG_HOME/mommy`, or
+/// `~/.config/mommy`
+pub fn config_dir() -> PathBuf {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(|| home_dir().join(".config"), PathBuf::from)
+        .join("mommy")
+}
+
+/// Directory for mommy's throwaway cache files: `$XDG_CACHE_HOME/mommy`, or
+/// `~/.cache/mommy`
+pub fn cache_dir() -> PathBuf {
+    std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map_or_else(|| home_dir().join(".cache"), PathBuf::from)
+        .join("mommy")
+}
+
+/// How widely opt-in state like the remembered mood is shared between runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scope {
+    Process,
+    Session,
+    #[default]
+    Global,
+}
+
+impl std::str::FromStr for Scope {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "process" => Ok(Self::Process),
+            "session" => Ok(Self::Session),
+            "global" => Ok(Self::Global),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Identifier of the current terminal session: `$TERM_SESSION_ID`, or the
+/// parent (usually the shell's) PID on Unix
+fn session_id() -> Option<String> {
+    std::env::var("TERM_SESSION_ID")
+        .ok()
+        .filter(|id| !id.is_empty())
+        .or_else(|| {
+            #[cfg(unix)]
+            return Some(std::os::unix::process::parent_id().to_string());
+            #[cfg(not(unix))]
+            None
+        })
+}
+
+/// Name of the directory holding state for `scope`, or `None` for global
+/// state. A scope without a session id falls back to global
+fn scope_key(scope: Scope, pid: u32, session: Option<&str>) -> Option<String> {
+    match scope {
+        Scope::Process => Some(format!("process-{pid}")),
+        Scope::Session => session.map(|id| {
+            let id: String = id
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect();
+            format!("session-{id}")
+        }),
+        Scope::Global => None,
+    }
+}
+
+/// Directory for files shared within `scope`, `global_dir` for global ones.
+/// Process and session files are short-lived, so they go to the temp dir
+/// instead of piling up
+fn scoped_dir(scope: Scope, global_dir: PathBuf) -> PathBuf {
+    match scope_key(scope, std::process::id(), session_id().as_deref()) {
+        Some(key) => std::env::temp_dir().join("mommy").join(key),
+        None => global_dir,
+    }
+}
+
+/// Directory for state shared within `scope`
+pub fn scoped_state_dir(scope: Scope) -> PathBuf {
+    scoped_dir(scope, state_dir())
+}
+
+/// Directory for cache files shared within `scope`
+pub fn scoped_cache_dir(scope: Scope) -> PathBuf {
+    scoped_dir(scope, cache_dir())
+}
+
+/// Where a single remembered value lives between runs, like the last mood for
+/// `REMEMBER_MOOD`
+pub trait TextStore {
+    fn load(&self) -> Option<String>;
+    fn store(&self, text: &str) -> io::Result<()>;
+}
+
+/// Remembers a value in a small file
+pub struct FileTextStore {
+    path: PathBuf,
+}
+
+impl FileTextStore {
+    /// The last mood, in `<state dir>/last_mood`
+    pub fn last_mood(scope: Scope) -> Self {
+        Self {
+            path: scoped_state_dir(scope).join("last_mood"),
+        }
+    }
+
+    /// Index of the last mood used by `MOOD_ROTATE`, in
+    /// `<state dir>/mood_idx`
+    pub fn mood_index(scope: Scope) -> Self {
+        Self {
+            path: scoped_state_dir(scope).join("mood_idx"),
+        }
+    }
+
+    /// The last line shown, in `<cache dir>/last`
+    pub fn last_line(scope: Scope) -> Self {
+        Self {
+            path: scoped_cache_dir(scope).join("last"),
+        }
+    }
+}
+
+impl TextStore for FileTextStore {
+    fn load(&self) -> Option<String> {
+        let text = fs::read_to_string(&self.path).ok()?.trim().to_string();
+        (!text.is_empty()).then_some(text)
+    }
+
+    fn store(&self, text: &str) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, text)
+    }
+}
+
+/// Progress of the `SHOWCASE` warm-up: how many runs it has been going and
+/// which lines were shown in the current cycle
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Showcase {
+    pub runs: u32,
+    pub seen: Vec<String>,
+}
+
+/// Where the `SHOWCASE` warm-up progress is kept between runs
+pub trait ShowcaseStore {
+    fn load(&self) -> Showcase;
+    fn store(&self, showcase: &Showcase) -> io::Result<()>;
+}
+
+/// Keeps the warm-up progress in `<state dir>/showcase.json`
+pub struct FileShowcaseStore {
+    path: PathBuf,
+}
+
+impl FileShowcaseStore {
+    pub fn new(scope: Scope) -> Self {
+        Self {
+            path: scoped_state_dir(scope).join("showcase.json"),
+        }
+    }
+}
+
+impl ShowcaseStore for FileShowcaseStore {
+    fn load(&self) -> Showcase {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn store(&self, showcase: &Showcase) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let contents = serde_json::to_string(showcase).map_err(io::Error::other)?;
+        fs::write(&self.path, contents)
+    }
+}
+
+#[cfg(feature = "beg")]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub enum Mood {
     #[default]
@@ -10,19 +215,16 @@ pub enum Mood {
     Angry,
 }
 
+#[cfg(feature = "beg")]
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct State {
     pub mood: Mood,
 }
 
+#[cfg(feature = "beg")]
 impl State {
     fn path() -> PathBuf {
-        // HOME is unset by default on native Windows (only USERPROFILE is guaranteed);
-        // fall back to the OS temp dir if neither is available.
-        let home = std::env::var("HOME")
-            .or_else(|_| std::env::var("USERPROFILE"))
-            .unwrap_or_else(|_| std::env::temp_dir().display().to_string());
-        PathBuf::from(home).join(".mommy.state")
+        home_dir().join(".mommy.state")
     }
 
     pub fn load() -> Result<Self, Box<dyn Error>> {
@@ -37,3 +239,35 @@ impl State {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_key() {
+        // Expect: process state keyed by PID
+        assert_eq!(
+            scope_key(Scope::Process, 4242, Some("w0t0p0:ABC")),
+            Some("process-4242".to_string())
+        );
+
+        // Expect: session state keyed by a path-safe session id
+        assert_eq!(
+            scope_key(Scope::Session, 4242, Some("w0t0p0:ABC/1")),
+            Some("session-w0t0p0_ABC_1".to_string())
+        );
+        assert_eq!(scope_key(Scope::Session, 4242, None), None);
+
+        // Expect: global state lives directly in the state dir
+        assert_eq!(scope_key(Scope::Global, 4242, Some("w0t0p0:ABC")), None);
+    }
+
+    #[test]
+    fn test_parse_scope() {
+        assert_eq!("process".parse(), Ok(Scope::Process));
+        assert_eq!("session".parse(), Ok(Scope::Session));
+        assert_eq!("global".parse(), Ok(Scope::Global));
+        assert_eq!("galaxy".parse::<Scope>(), Err(()));
+    }
+}
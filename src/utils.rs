@@ -1,5 +1,61 @@
-use crate::config::ConfigMommy;
-use std::io::{self, Write};
+use crate::{
+    color::{colorize_stderr, style_warning},
+    config::ConfigMommy,
+    random,
+};
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::LazyLock;
+use std::{env, fs};
+
+// `{user}` and `{host}` don't change during a run, so look them up once
+static USER: LazyLock<String> = LazyLock::new(read_user);
+static HOST: LazyLock<String> = LazyLock::new(|| read_host(Path::new("/etc/hostname")));
+
+/// Name of the user running mommy, from `$USER` or `$USERNAME`
+fn read_user() -> String {
+    ["USER", "USERNAME"]
+        .iter()
+        .find_map(|key| env::var(key).ok().filter(|v| !v.trim().is_empty()))
+        .unwrap_or_else(|| "you".to_string())
+}
+
+/// Name of this machine, from `$HOSTNAME` or else `hostname_file` on unix
+fn read_host(hostname_file: &Path) -> String {
+    env::var("HOSTNAME")
+        .ok()
+        .or_else(|| {
+            if cfg!(unix) {
+                fs::read_to_string(hostname_file).ok()
+            } else {
+                None
+            }
+        })
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| "here".to_string())
+}
+
+// Local hours at which each `{time_greeting}` starts, night wrapping around
+// midnight until morning
+const MORNING_START: u8 = 5;
+const AFTERNOON_START: u8 = 12;
+const EVENING_START: u8 = 17;
+const NIGHT_START: u8 = 22;
+
+// Looked up only when a template asks for `{time_greeting}`
+static LOCAL_HOUR: LazyLock<u8> = LazyLock::new(crate::localtime::local_hour);
+
+/// Greeting for a local hour (0-23)
+fn greeting_for_hour(hour: u8) -> &'static str {
+    match hour {
+        h if (MORNING_START..AFTERNOON_START).contains(&h) => "good morning",
+        h if (AFTERNOON_START..EVENING_START).contains(&h) => "good afternoon",
+        h if (EVENING_START..NIGHT_START).contains(&h) => "good evening",
+        _ => "good night",
+    }
+}
 
 /// Pick a random string from a pre-parsed Vec<String>
 /// Returns a reference to avoid cloning
@@ -8,19 +64,157 @@ pub fn random_vec_pick(vec: &[String]) -> Option<&str> {
     if vec.is_empty() {
         None
     } else {
-        let idx = fastrand::usize(..vec.len());
+        let idx = random::usize(..vec.len());
         Some(&vec[idx])
     }
 }
 
+/// Index that `roll` (below the total weight) lands on when the weights are
+/// laid out one after another. A weight of 0 counts as 1, like for lines
+fn weighted_index(weights: &[u32], mut roll: u64) -> usize {
+    weights
+        .iter()
+        .position(|&w| {
+            let w = u64::from(w.max(1));
+            if roll < w {
+                return true;
+            }
+            roll -= w;
+            false
+        })
+        .unwrap_or(weights.len().saturating_sub(1))
+}
+
+/// Pick a random string with a chance proportional to its weight. Falls back
+/// to a uniform pick if `weights` doesn't have one weight per value
+pub fn random_weighted_pick<'a>(values: &'a [String], weights: &[u32]) -> Option<&'a str> {
+    if values.len() != weights.len() {
+        return random_vec_pick(values);
+    }
+    let total: u64 = weights.iter().map(|&w| u64::from(w.max(1))).sum();
+    if total == 0 {
+        return None;
+    }
+    values
+        .get(weighted_index(weights, random::u64(..total)))
+        .map(String::as_str)
+}
+
+/// Pick a value for one placeholder occurrence. With a locked identity the
+/// first pick is remembered and reused for the rest of the render
+#[inline]
+fn pick_value<'a>(
+    locked: &mut Option<&'a str>,
+    values: &'a [String],
+    weights: &[u32],
+    fallback: &'a str,
+    lock: bool,
+) -> &'a str {
+    if let Some(value) = *locked {
+        return value;
+    }
+    let value = random_weighted_pick(values, weights).unwrap_or(fallback);
+    if lock {
+        *locked = Some(value);
+    }
+    value
+}
+
+/// State of a flag usable in `{?flag:text}` fragments, `None` if unknown
+fn template_flag(flag: &str, config: &ConfigMommy) -> Option<bool> {
+    match flag {
+        "needy" => Some(config.needy),
+        "beg" => Some(config.begging),
+        "ci" => Some(config.ci),
+        "interrupted" => Some(config.interrupted),
+        "only_negative" => Some(config.only_negative),
+        "only_positive" => Some(config.only_positive),
+        _ => None,
+    }
+}
+
+/// Replace the placeholders in `template` with values picked from `config`
+///
+/// ```
+/// let mut config = shell_mommy::load_config();
+/// config.roles = vec!["daddy".to_string()];
+/// config.lock_identity = false;
+/// assert_eq!(shell_mommy::fill_template("{roles} is here~", &config), "daddy is here~");
+/// ```
 #[inline]
 pub fn fill_template(template: &str, config: &ConfigMommy) -> String {
-    // Pick random values from pre-parsed config vectors
-    // Use first element as fallback if vector is somehow empty
-    let role = random_vec_pick(&config.roles).unwrap_or("mommy");
-    let pronoun = random_vec_pick(&config.pronouns).unwrap_or("her");
-    let little = random_vec_pick(&config.little).unwrap_or("girl");
-    let emote = random_vec_pick(&config.emotes).unwrap_or("💖");
+    fill(template, config, None)
+}
+
+/// Like `fill_template`, also replacing `{exit_code}` with the wrapped
+/// command's exit code
+#[inline]
+pub fn fill_template_with_code(template: &str, config: &ConfigMommy, exit_code: i32) -> String {
+    fill(template, config, Some(exit_code))
+}
+
+/// Push a placeholder's value with its `{name:filter}` filter applied:
+/// `cap` uppercases the first letter, `upper` and `lower` the whole value.
+/// Unknown filters leave the value as is
+fn push_filtered(result: &mut String, value: &str, filter: Option<&str>) {
+    match filter {
+        Some("cap") => {
+            let mut chars = value.chars();
+            if let Some(first) = chars.next() {
+                result.extend(first.to_uppercase());
+                result.push_str(chars.as_str());
+            }
+        }
+        Some("upper") => result.push_str(&value.to_uppercase()),
+        Some("lower") => result.push_str(&value.to_lowercase()),
+        _ => result.push_str(value),
+    }
+}
+
+/// Placeholders `fill_template` knows how to fill
+const PLACEHOLDERS: &[&str] = &[
+    "roles",
+    "pronouns",
+    "little",
+    "emotes",
+    "exit_code",
+    "user",
+    "host",
+    "time_greeting",
+    "warn_count",
+    "error_count",
+];
+
+/// Placeholders in a template that `fill_template` would leave untouched,
+/// including `{?flag:text}` fragments with an unknown flag (as `?flag`).
+/// `{{` escapes aren't placeholders
+pub fn unknown_placeholders<'a>(template: &'a str, config: &ConfigMommy) -> Vec<&'a str> {
+    template
+        .split("{{")
+        .flat_map(|chunk| chunk.split('{').skip(1))
+        .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+        // Filters (`{roles:cap}`) and fragment text don't change which
+        // placeholder it is
+        .map(|name| name.split_once(':').map_or(name, |(name, _)| name))
+        .filter(|name| match name.strip_prefix('?') {
+            Some(flag) => template_flag(flag.trim(), config).is_none(),
+            None => !PLACEHOLDERS.contains(name),
+        })
+        .collect()
+}
+
+/// Render a template. `{exit_code}` is left as is when no code is known
+fn fill(template: &str, config: &ConfigMommy, exit_code: Option<i32>) -> String {
+    // Values are picked from the pre-parsed config vectors per occurrence
+    // (or once per render with LOCK_IDENTITY), with a fallback if a vector is
+    // somehow empty
+    let lock = config.lock_identity;
+    let (mut role, mut pronoun, mut little, mut emote) = (None, None, None, None);
+    let code = exit_code.map(|code| code.to_string());
+    let (warn_count, error_count) = (
+        config.warn_count.to_string(),
+        config.error_count.to_string(),
+    );
 
     // Single-pass replacement to avoid intermediate allocations
     // Pre-allocate with extra capacity for replacements
@@ -30,8 +224,8 @@ pub fn fill_template(template: &str, config: &ConfigMommy) -> String {
 
     let mut i = 0;
     while i < bytes.len() {
-        if bytes[i] == b'{' {
-            // Add everything before this '{', converting newlines to spaces
+        if bytes[i] == b'{' || bytes[i..].starts_with(b"}}") {
+            // Add everything before this brace, converting newlines to spaces
             let part = &template[last_end..i];
             let mut parts = part.split('\n');
             if let Some(first) = parts.next() {
@@ -42,26 +236,85 @@ pub fn fill_template(template: &str, config: &ConfigMommy) -> String {
                 }
             }
 
-            // Check which placeholder this is
+            // Check which placeholder this is. `{{` and `}}` are escapes for
+            // literal braces
             let remaining = &template[i..];
-            if remaining.starts_with("{roles}") {
-                result.push_str(role);
-                i += 7;
+            if remaining.starts_with("{{") || remaining.starts_with("}}") {
+                result.push_str(&remaining[..1]);
+                i += 2;
                 last_end = i;
-            } else if remaining.starts_with("{pronouns}") {
-                result.push_str(pronoun);
-                i += 10;
-                last_end = i;
-            } else if remaining.starts_with("{little}") {
-                result.push_str(little);
-                i += 8;
+            } else if let Some((value, filter, len)) =
+                remaining[1..].split_once('}').and_then(|(inner, _)| {
+                    // `{name}` or `{name:filter}`
+                    let (name, filter) = match inner.split_once(':') {
+                        Some((name, filter)) => (name, Some(filter)),
+                        None => (inner, None),
+                    };
+                    let value = match name {
+                        "roles" => Some(pick_value(
+                            &mut role,
+                            &config.roles,
+                            &config.role_weights,
+                            "mommy",
+                            lock,
+                        )),
+                        "pronouns" => Some(pick_value(
+                            &mut pronoun,
+                            &config.pronouns,
+                            &config.pronoun_weights,
+                            "her",
+                            lock,
+                        )),
+                        "little" => Some(pick_value(
+                            &mut little,
+                            &config.little,
+                            &config.little_weights,
+                            "girl",
+                            lock,
+                        )),
+                        "emotes" if config.no_emotes => Some(""),
+                        "emotes" => Some(pick_value(
+                            &mut emote,
+                            &config.emotes,
+                            &config.emote_weights,
+                            "💖",
+                            lock,
+                        )),
+                        "user" => Some(USER.as_str()),
+                        "host" => Some(HOST.as_str()),
+                        "time_greeting" => Some(greeting_for_hour(*LOCAL_HOUR)),
+                        "exit_code" => code.as_deref(),
+                        "warn_count" => Some(warn_count.as_str()),
+                        "error_count" => Some(error_count.as_str()),
+                        _ => None,
+                    };
+                    Some((value?, filter, inner.len() + 2))
+                })
+            {
+                // A placeholder that renders as nothing doesn't leave its
+                // leading space dangling
+                if value.is_empty() {
+                    result.truncate(result.trim_end_matches(' ').len());
+                }
+                push_filtered(&mut result, value, filter);
+                i += len;
                 last_end = i;
-            } else if remaining.starts_with("{emotes}") {
-                result.push_str(emote);
-                i += 8;
+            } else if let Some(end) = remaining
+                .strip_prefix("{?")
+                .and_then(|fragment| fragment.find('}'))
+            {
+                // Conditional fragment: `{?flag:text}` keeps the text only
+                // while the flag is active
+                let fragment = &remaining[2..end + 2];
+                let (flag, text) = fragment.split_once(':').unwrap_or((fragment, ""));
+                // An unknown flag counts as inactive, see `unknown_placeholders`
+                if template_flag(flag.trim(), config) == Some(true) {
+                    result.push_str(text);
+                }
+                i += end + 3;
                 last_end = i;
             } else {
-                // Not a recognized placeholder, keep the '{'
+                // Not a recognized placeholder, keep the brace
                 last_end = i;
                 i += 1;
             }
@@ -85,12 +338,155 @@ pub fn fill_template(template: &str, config: &ConfigMommy) -> String {
     result
 }
 
-pub fn graceful_print<T: std::fmt::Display>(s: T) {
-    if writeln!(io::stderr(), "{s}").is_err() {
+/// Collapse runs of spaces into one and trim both ends of every line,
+/// keeping the line breaks themselves
+pub fn normalize_whitespace(s: &str) -> String {
+    s.split('\n')
+        .map(|line| {
+            line.split(' ')
+                .filter(|w| !w.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Print a line to the configured stream, exiting quietly once nobody is
+/// reading anymore
+pub fn graceful_print<T: std::fmt::Display>(s: T, output: Output) {
+    let written = match output {
+        Output::Stderr => graceful_print_to(io::stderr().lock(), s),
+        Output::Stdout => graceful_print_to(io::stdout().lock(), s),
+        #[cfg(unix)]
+        Output::Fd(fd) => output.is_writable() && graceful_print_to(&*borrow_fd(fd), s),
+    };
+    if !written {
         std::process::exit(0);
     }
 }
 
+/// Write a line to any writer, reporting whether it went through instead of
+/// failing, so a closed pipe can end the run quietly
+pub fn graceful_print_to<W: Write, T: std::fmt::Display>(mut writer: W, s: T) -> bool {
+    writeln!(writer, "{s}")
+        .and_then(|()| writer.flush())
+        .is_ok()
+}
+
+/// Stream affirmations are written to, stderr unless `OUTPUT` says otherwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Output {
+    #[default]
+    Stderr,
+    Stdout,
+    /// A file descriptor the caller opened for mommy, like `fd:3`
+    #[cfg(unix)]
+    Fd(i32),
+}
+
+impl Output {
+    pub fn is_terminal(self) -> bool {
+        match self {
+            Self::Stderr => io::stderr().is_terminal(),
+            Self::Stdout => io::stdout().is_terminal(),
+            // SAFETY: isatty only inspects the descriptor
+            #[cfg(unix)]
+            Self::Fd(fd) => unsafe { libc::isatty(fd) == 1 },
+        }
+    }
+
+    /// Whether lines can be written to this stream. A file descriptor has to
+    /// be open, and not read-only
+    pub fn is_writable(self) -> bool {
+        match self {
+            Self::Stderr | Self::Stdout => true,
+            #[cfg(unix)]
+            Self::Fd(fd) => {
+                // SAFETY: F_GETFL only reads the descriptor's flags, and fails
+                // for one that isn't open
+                let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+                flags != -1 && flags & libc::O_ACCMODE != libc::O_RDONLY
+            }
+        }
+    }
+}
+
+/// Borrow an open file descriptor as a file without taking it over, so it
+/// stays open for whoever passed it to mommy
+#[cfg(unix)]
+fn borrow_fd(fd: i32) -> std::mem::ManuallyDrop<fs::File> {
+    use std::os::fd::FromRawFd;
+    // SAFETY: callers check the descriptor is open first, and ManuallyDrop
+    // keeps it from being closed here
+    std::mem::ManuallyDrop::new(unsafe { fs::File::from_raw_fd(fd) })
+}
+
+impl std::str::FromStr for Output {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stderr" => Ok(Self::Stderr),
+            "stdout" => Ok(Self::Stdout),
+            #[cfg(unix)]
+            _ => s
+                .strip_prefix("fd:")
+                .and_then(|fd| fd.parse().ok())
+                .filter(|fd| *fd >= 0)
+                .map(Self::Fd)
+                .ok_or(()),
+            #[cfg(not(unix))]
+            _ => Err(()),
+        }
+    }
+}
+
+/// Print an operational message (warning, error) to stderr, in the warn
+/// color when colors are on
+pub fn warn(config: &ConfigMommy, message: &str) {
+    let color = colorize_stderr(config.force_color).then_some(config.warn_color);
+    eprintln!("{}", style_warning(message, color));
+}
+
+/// Pager to run from a `PAGER` value, split into program and arguments.
+/// Defaults to `less -R` so colors survive
+pub fn pager_command(pager: Option<&str>) -> Vec<String> {
+    let command: Vec<String> = pager
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(str::to_string)
+        .collect();
+    if command.is_empty() {
+        vec!["less".to_string(), "-R".to_string()]
+    } else {
+        command
+    }
+}
+
+/// Print to stdout, through the user's pager when `use_pager` is set and
+/// stdout is a terminal. Falls back to printing directly if no pager starts
+pub fn print_paged(output: &str, use_pager: bool) {
+    if use_pager && io::stdout().is_terminal() {
+        let command = pager_command(std::env::var("PAGER").ok().as_deref());
+        if let Ok(mut child) = Command::new(&command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .spawn()
+        {
+            // Quitting the pager early closes its stdin, which is fine
+            if let Some(mut stdin) = child.stdin.take() {
+                let _ = writeln!(stdin, "{output}");
+            }
+            let _ = child.wait();
+            return;
+        }
+    }
+
+    // A closed pipe (e.g. `| head`) just means nobody wants the rest
+    let _ = writeln!(io::stdout(), "{output}");
+}
+
 /// Robust shell quoting for single arguments.
 /// Surrounds the argument with single quotes and escapes any existing single quotes.
 pub fn shell_quote(s: &str) -> String {
@@ -116,10 +512,104 @@ pub fn shell_quote(s: &str) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::load_config;
+    use crate::config::{load_config, ENV_TEST_LOCK};
+
+    #[test]
+    fn test_unknown_placeholders() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let config = load_config();
+        assert_eq!(
+            unknown_placeholders("{roles} loves {her} {little}~ {?needy:ok}", &config),
+            vec!["her"]
+        );
+        assert!(unknown_placeholders("no placeholders here", &config).is_empty());
+        assert!(unknown_placeholders("literal {{braces}} {roles}", &config).is_empty());
+        assert!(unknown_placeholders("{roles:cap} {little:upper}", &config).is_empty());
+
+        // Expect: a misspelled flag is caught, known ones aren't
+        assert_eq!(
+            unknown_placeholders("{?neddy:since you asked, }good {?beg:~}", &config),
+            vec!["?neddy"]
+        );
+    }
+
+    #[test]
+    fn test_weighted_index() {
+        // Expect: rolls 0-3 land on the first value, 4 on the second
+        let weights = [4, 1];
+        let picks: Vec<_> = (0..5).map(|roll| weighted_index(&weights, roll)).collect();
+        assert_eq!(picks, vec![0, 0, 0, 0, 1]);
+
+        // Expect: a weight of 0 counts as 1
+        assert_eq!(weighted_index(&[0, 2], 0), 0);
+        assert_eq!(weighted_index(&[0, 2], 1), 1);
+    }
+
+    #[test]
+    fn test_random_weighted_pick() {
+        fastrand::seed(7);
+        let values = vec!["mommy".to_string(), "daddy".to_string()];
+
+        // Expect: roughly 80% mommy with mommy*4/daddy*1
+        let mommy = (0..10_000)
+            .filter(|_| random_weighted_pick(&values, &[4, 1]) == Some("mommy"))
+            .count();
+        assert!((7_500..8_500).contains(&mommy), "{mommy}");
+
+        // Expect: mismatched weights fall back to a uniform pick
+        let mommy = (0..10_000)
+            .filter(|_| random_weighted_pick(&values, &[1]) == Some("mommy"))
+            .count();
+        assert!((4_500..5_500).contains(&mommy), "{mommy}");
+        assert_eq!(random_weighted_pick(&[], &[]), None);
+    }
+
+    #[test]
+    fn test_greeting_for_hour() {
+        let expected = |hour: u8| match hour {
+            0..=4 | 22..=23 => "good night",
+            5..=11 => "good morning",
+            12..=16 => "good afternoon",
+            _ => "good evening",
+        };
+
+        // Expect: every hour of the day gets its greeting
+        for hour in 0..24 {
+            assert_eq!(greeting_for_hour(hour), expected(hour), "hour {hour}");
+        }
+
+        // Expect: the thresholds are where each greeting starts
+        assert_eq!(greeting_for_hour(MORNING_START), "good morning");
+        assert_eq!(greeting_for_hour(AFTERNOON_START), "good afternoon");
+        assert_eq!(greeting_for_hour(EVENING_START), "good evening");
+        assert_eq!(greeting_for_hour(NIGHT_START), "good night");
+        assert!(*LOCAL_HOUR < 24);
+    }
+
+    #[test]
+    fn test_fill_template_no_emotes() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.roles = vec!["mommy".to_string()];
+        config.no_emotes = true;
+
+        // Expect: no emote and no space left dangling where it was
+        assert_eq!(fill_template("good job~ {emotes}", &config), "good job~");
+        assert_eq!(
+            fill_template("{roles} {emotes} is proud~", &config),
+            "mommy is proud~"
+        );
+        assert_eq!(fill_template("{emotes}", &config), "");
+
+        // Expect: emotes are back once they're on
+        config.no_emotes = false;
+        config.emotes = vec!["💖".to_string()];
+        assert_eq!(fill_template("good job~ {emotes}", &config), "good job~ 💖");
+    }
 
     #[test]
     fn test_fill_template() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
         fastrand::seed(42);
         let mut config = load_config();
         // Config now has pre-parsed Vec<String> fields
@@ -135,8 +625,221 @@ mod tests {
         assert_eq!(template, "mommy thinks his baby earned a big hug~ ❤️‍🔥");
     }
 
+    #[test]
+    fn test_fill_template_lock_identity() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.roles = ["mommy", "daddy", "parent", "auntie"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let template = "{roles} says {roles}";
+
+        // Expect: without the lock the two occurrences differ for some seed
+        config.lock_identity = false;
+        let differs = (0..20).any(|seed| {
+            fastrand::seed(seed);
+            let output = fill_template(template, &config);
+            let (first, second) = output.split_once(" says ").unwrap();
+            first != second
+        });
+        assert!(differs, "expected independent picks per occurrence");
+
+        // Expect: with the lock both occurrences always match
+        config.lock_identity = true;
+        for seed in 0..20 {
+            fastrand::seed(seed);
+            let output = fill_template(template, &config);
+            let (first, second) = output.split_once(" says ").unwrap();
+            assert_eq!(first, second);
+        }
+    }
+
+    #[test]
+    fn test_fill_template_emotes_per_occurrence() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.emotes = ["💖", "💗", "💓", "💞"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        config.emote_weights.clear();
+        config.no_emotes = false;
+        config.lock_identity = false;
+
+        // Expect: each {emotes} gets its own pick, so they differ for some seed
+        let differs = (0..20).any(|seed| {
+            fastrand::seed(seed);
+            let output = fill_template("{emotes} and {emotes}", &config);
+            let (first, second) = output.split_once(" and ").unwrap();
+            assert!(config.emotes.iter().any(|emote| emote == first));
+            assert!(config.emotes.iter().any(|emote| emote == second));
+            first != second
+        });
+        assert!(differs, "expected independent emotes per occurrence");
+    }
+
+    #[test]
+    fn test_fill_template_exit_code() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let config = load_config();
+
+        // Expect: the code is rendered, every time it appears
+        assert_eq!(
+            fill_template_with_code("you exited with {exit_code}~ ({exit_code})", &config, 127),
+            "you exited with 127~ (127)"
+        );
+        // Expect: templates without it render the same as fill_template
+        assert_eq!(
+            fill_template_with_code("good job~", &config, 1),
+            fill_template("good job~", &config)
+        );
+        // Expect: without a code the placeholder is left alone
+        assert_eq!(fill_template("{exit_code}", &config), "{exit_code}");
+    }
+
+    #[test]
+    fn test_fill_template_user_host() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let config = load_config();
+        assert_eq!(
+            fill_template("good job {user}~ {host} is proud", &config),
+            format!("good job {}~ {} is proud", *USER, *HOST)
+        );
+    }
+
+    /// Puts the saved env vars back when dropped, even if the test fails
+    struct SavedEnv(Vec<(&'static str, Option<std::ffi::OsString>)>);
+
+    impl SavedEnv {
+        fn save(keys: &[&'static str]) -> Self {
+            Self(keys.iter().map(|&key| (key, env::var_os(key))).collect())
+        }
+    }
+
+    impl Drop for SavedEnv {
+        fn drop(&mut self) {
+            for (key, value) in &self.0 {
+                // SAFETY: still under ENV_TEST_LOCK, see the tests below
+                unsafe {
+                    match value {
+                        Some(value) => env::set_var(key, value),
+                        None => env::remove_var(key),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_user() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let _saved = SavedEnv::save(&["USER", "USERNAME"]);
+        // SAFETY: ENV_TEST_LOCK serializes this with the other tests that
+        // change the environment, and `SavedEnv` restores it afterwards
+        unsafe {
+            env::set_var("USER", "minty");
+            env::set_var("USERNAME", "other");
+        }
+        assert_eq!(read_user(), "minty");
+
+        unsafe {
+            env::remove_var("USER");
+        }
+        assert_eq!(read_user(), "other");
+
+        // Expect: nothing set falls back to "you"
+        unsafe {
+            env::remove_var("USERNAME");
+        }
+        assert_eq!(read_user(), "you");
+    }
+
+    #[test]
+    fn test_read_host() {
+        let missing = Path::new("/nonexistent/mommy-hostname");
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let _saved = SavedEnv::save(&["HOSTNAME"]);
+        // SAFETY: ENV_TEST_LOCK serializes this with the other tests that
+        // change the environment, and `SavedEnv` restores it afterwards
+        unsafe {
+            env::set_var("HOSTNAME", "cozy-box");
+        }
+        assert_eq!(read_host(missing), "cozy-box");
+
+        // Expect: nothing set and no hostname file falls back to "here"
+        unsafe {
+            env::remove_var("HOSTNAME");
+        }
+        assert_eq!(read_host(missing), "here");
+    }
+
+    #[test]
+    fn test_fill_template_brace_escapes() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.roles = vec!["mommy".to_string()];
+        config.little = vec!["girl".to_string()];
+
+        // Expect: doubled braces render as literal ones, not as a placeholder
+        assert_eq!(fill_template("{{roles}}", &config), "{roles}");
+        // Expect: escapes and real placeholders mix
+        assert_eq!(
+            fill_template(
+                "{roles} says {{little}} means {little}~ {{{roles}}}",
+                &config
+            ),
+            "mommy says {little} means girl~ {mommy}"
+        );
+        // Expect: unmatched braces are kept as they are
+        assert_eq!(fill_template("good {little} {", &config), "good girl {");
+        assert_eq!(fill_template("good {little} }", &config), "good girl }");
+        assert_eq!(fill_template("}{", &config), "}{");
+    }
+
+    #[test]
+    fn test_fill_template_filters() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.roles = vec!["évi".to_string()];
+        config.pronouns = vec!["her".to_string()];
+        config.little = vec!["Ümit".to_string()];
+        config.emotes = vec!["💖".to_string()];
+
+        // Expect: each filter on each placeholder, on char boundaries
+        let cases = [
+            ("{roles:cap}", "Évi"),
+            ("{roles:upper}", "ÉVI"),
+            ("{roles:lower}", "évi"),
+            ("{pronouns:cap}", "Her"),
+            ("{pronouns:upper}", "HER"),
+            ("{pronouns:lower}", "her"),
+            ("{little:cap}", "Ümit"),
+            ("{little:upper}", "ÜMIT"),
+            ("{little:lower}", "ümit"),
+            ("{emotes:cap}", "💖"),
+            ("{emotes:upper}", "💖"),
+            ("{emotes:lower}", "💖"),
+        ];
+        for (template, expected) in cases {
+            assert_eq!(fill_template(template, &config), expected, "{template}");
+        }
+        assert_eq!(
+            fill_template_with_code("{exit_code:upper}", &config, 2),
+            "2"
+        );
+
+        // Expect: unknown filters render the raw value, plain forms still work
+        assert_eq!(fill_template("{roles:sparkly}", &config), "évi");
+        assert_eq!(
+            fill_template("{roles:cap} loves {pronouns} {little}", &config),
+            "Évi loves her Ümit"
+        );
+    }
+
     #[test]
     fn test_unknown_placeholder_preservation() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
         let mut config = load_config();
         config.roles = vec!["mommy".to_string()];
 
@@ -148,6 +851,23 @@ mod tests {
         assert_eq!(result, "Hello {unknown} world");
     }
 
+    #[test]
+    fn test_normalize_whitespace() {
+        assert_eq!(normalize_whitespace("good  job~  "), "good job~");
+        assert_eq!(normalize_whitespace("  you   did it "), "you did it");
+        assert_eq!(normalize_whitespace("fine"), "fine");
+        assert_eq!(normalize_whitespace(""), "");
+    }
+
+    #[test]
+    fn test_normalize_whitespace_keeps_newlines() {
+        assert_eq!(
+            normalize_whitespace("good {little}~ \n  so  proud"),
+            "good {little}~\nso proud"
+        );
+        assert_eq!(normalize_whitespace("a\n\nb"), "a\n\nb");
+    }
+
     #[test]
     fn test_shell_quote() {
         assert_eq!(shell_quote(""), "''");
@@ -157,4 +877,190 @@ mod tests {
         assert_eq!(shell_quote("; id"), "'; id'");
         assert_eq!(shell_quote("'"), "''\\'''");
     }
+
+    #[test]
+    fn test_fill_template_conditional() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.little = vec!["girl".to_string()];
+
+        // Expect: the fragment shows up only while its flag is active
+        config.needy = true;
+        assert_eq!(
+            fill_template("{?needy:since you asked nicely, }good {little}~", &config),
+            "since you asked nicely, good girl~"
+        );
+        config.needy = false;
+        assert_eq!(
+            fill_template("{?needy:since you asked nicely, }good {little}~", &config),
+            "good girl~"
+        );
+
+        // Expect: several fragments side by side, each on its own flag
+        config.needy = true;
+        config.ci = false;
+        assert_eq!(fill_template("{?needy:a}{?ci:b}{?needy:c}", &config), "ac");
+
+        // Expect: unknown flags render as nothing
+        assert_eq!(fill_template("hi{?gentle:, softly}~", &config), "hi~");
+
+        // Expect: beg follows whether the user said please, not the build
+        config.begging = false;
+        assert_eq!(fill_template("{?beg:thank you, }ok~", &config), "ok~");
+        config.begging = true;
+        assert_eq!(
+            fill_template("{?beg:thank you, }ok~", &config),
+            "thank you, ok~"
+        );
+    }
+
+    #[test]
+    fn test_pager_command() {
+        // Expect: less keeping colors when PAGER is unset or blank
+        assert_eq!(pager_command(None), vec!["less", "-R"]);
+        assert_eq!(pager_command(Some("  ")), vec!["less", "-R"]);
+
+        // Expect: PAGER split into program and arguments
+        assert_eq!(pager_command(Some("most")), vec!["most"]);
+        assert_eq!(pager_command(Some("less -FRX")), vec!["less", "-FRX"]);
+    }
+
+    #[test]
+    fn test_fill_diagnostic_counts() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.warn_count = 12;
+        config.error_count = 0;
+
+        // Expect: the counts from captured output fill their placeholders
+        assert_eq!(
+            fill_template("{warn_count} warnings, {error_count} errors~", &config),
+            "12 warnings, 0 errors~"
+        );
+    }
+
+    #[test]
+    fn test_parse_output() {
+        assert_eq!("stderr".parse(), Ok(Output::Stderr));
+        assert_eq!("stdout".parse(), Ok(Output::Stdout));
+        assert_eq!("printer".parse::<Output>(), Err(()));
+        assert_eq!("fd:".parse::<Output>(), Err(()));
+        assert_eq!("fd:-1".parse::<Output>(), Err(()));
+
+        // Expect: a pre-opened descriptor on unix, nothing elsewhere
+        #[cfg(unix)]
+        assert_eq!("fd:3".parse(), Ok(Output::Fd(3)));
+        #[cfg(not(unix))]
+        assert_eq!("fd:3".parse::<Output>(), Err(()));
+    }
+
+    /// Print a probe line the way the binary prints JSON, to whatever stream
+    /// `OUTPUT` names, when run as the child of `test_output_streams`
+    #[test]
+    fn output_stream_child() {
+        if env::var_os("MOMMY_TEST_OUTPUT_CHILD").is_some() {
+            graceful_print(r#"{"probe":1}"#, load_config().output);
+        }
+    }
+
+    /// Run `output_stream_child` in a fresh test process with `output` as
+    /// `SHELL_MOMMYS_OUTPUT`
+    fn output_stream_command(output: &str) -> Command {
+        let mut command = Command::new(env::current_exe().unwrap());
+        command
+            .args([
+                "--exact",
+                "utils::tests::output_stream_child",
+                "--nocapture",
+            ])
+            .args(["--test-threads", "1"])
+            .env("MOMMY_TEST_OUTPUT_CHILD", "1")
+            .env("SHELL_MOMMYS_OUTPUT", output)
+            .env(
+                "XDG_CONFIG_HOME",
+                env::temp_dir().join("mommy-test-no-config"),
+            );
+        command
+    }
+
+    #[test]
+    fn test_output_streams() {
+        let probe = r#"{"probe":1}"#;
+
+        // Expect: the line lands on the named stream, and only there
+        let out = output_stream_command("stdout").output().unwrap();
+        assert!(String::from_utf8_lossy(&out.stdout).contains(probe));
+        assert!(!String::from_utf8_lossy(&out.stderr).contains(probe));
+
+        let out = output_stream_command("stderr").output().unwrap();
+        assert!(String::from_utf8_lossy(&out.stderr).contains(probe));
+        assert!(!String::from_utf8_lossy(&out.stdout).contains(probe));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_fd() {
+        use std::os::fd::AsRawFd;
+        use std::os::unix::process::CommandExt;
+
+        let path = env::temp_dir().join(format!("mommy-test-fd-{}.json", std::process::id()));
+        let file = fs::File::create(&path).unwrap();
+        let raw = file.as_raw_fd();
+        let mut command = output_stream_command("fd:3");
+        // SAFETY: dup2 and fcntl are async-signal-safe and only touch the
+        // child's descriptors. Clearing close-on-exec covers `raw` already
+        // being 3, where dup2 changes nothing
+        unsafe {
+            command.pre_exec(move || {
+                if libc::dup2(raw, 3) == -1 || libc::fcntl(3, libc::F_SETFD, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        // Expect: the line goes to descriptor 3, not stdout or stderr
+        let out = command.output().unwrap();
+        drop(file);
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(written.trim(), r#"{"probe":1}"#);
+        assert!(!String::from_utf8_lossy(&out.stdout).contains("probe\""));
+        assert!(!String::from_utf8_lossy(&out.stderr).contains("probe\""));
+
+        // Expect: descriptors that aren't open or are read-only are refused
+        assert!(Output::Stdout.is_writable());
+        assert!(Output::Stderr.is_writable());
+        assert!(!Output::Fd(9999).is_writable());
+        let read_only = fs::File::open("/dev/null").unwrap();
+        assert!(!Output::Fd(read_only.as_raw_fd()).is_writable());
+        let writable = fs::OpenOptions::new()
+            .write(true)
+            .open("/dev/null")
+            .unwrap();
+        assert!(Output::Fd(writable.as_raw_fd()).is_writable());
+    }
+
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_graceful_print_to() {
+        // Expect: the line lands in the writer with a trailing newline
+        let mut buffer = Vec::new();
+        assert!(graceful_print_to(&mut buffer, "good girl~"));
+        assert_eq!(buffer, b"good girl~\n");
+
+        // Expect: a closed pipe is reported instead of panicking
+        assert!(!graceful_print_to(ClosedPipe, "good girl~"));
+    }
 }
@@ -0,0 +1,256 @@
+//! `validate` subcommand: check the whole configuration in one pass, or a
+//! single affirmations file.
+
+use crate::{
+    affirmations::{affirmation_texts, mood_summaries, split_affirmation_paths, MoodSummary},
+    color::{color_from_rgb_entry, parse_gradient, resolve_color, STYLE_ATTRS},
+    config::ConfigMommy,
+    utils::unknown_placeholders,
+};
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+/// A problem found while validating. Errors break something, warnings are
+/// probably a mistake
+#[derive(Debug, PartialEq)]
+enum Issue {
+    Error(String),
+    Warning(String),
+}
+
+/// Check the custom affirmations file at `path`: it exists, parses, has no
+/// empty pools and only uses known placeholders. Returns the moods it found
+fn validate_affirmations(
+    path: &str,
+    config: &ConfigMommy,
+    issues: &mut Vec<Issue>,
+) -> Vec<MoodSummary> {
+    let json_str = match fs::read_to_string(path) {
+        Ok(json_str) => json_str,
+        Err(e) => {
+            issues.push(Issue::Error(format!(
+                "AFFIRMATIONS: can't read {path}: {e}"
+            )));
+            return Vec::new();
+        }
+    };
+    let (texts, moods) = match affirmation_texts(Path::new(path), &json_str)
+        .and_then(|texts| Ok((texts, mood_summaries(Path::new(path), &json_str)?)))
+    {
+        Ok(found) => found,
+        Err(e) => {
+            issues.push(Issue::Error(format!(
+                "AFFIRMATIONS: {path} isn't a valid affirmations file: {e}"
+            )));
+            return Vec::new();
+        }
+    };
+
+    for mood in &moods {
+        for (pool, count) in [("positive", mood.positive), ("negative", mood.negative)] {
+            if count == 0 {
+                issues.push(Issue::Warning(format!(
+                    "AFFIRMATIONS: mood '{}' has no {pool} lines",
+                    mood.name
+                )));
+            }
+        }
+    }
+    for text in &texts {
+        for name in unknown_placeholders(text, config) {
+            issues.push(Issue::Warning(format!(
+                "AFFIRMATIONS: unknown placeholder {{{name}}} in \"{text}\""
+            )));
+        }
+    }
+    moods
+}
+
+fn validate(config: &ConfigMommy) -> Vec<Issue> {
+    let mut issues = Vec::new();
+
+    for color in &config.colors {
+        if let Err(unknown) = resolve_color(color) {
+            issues.push(Issue::Error(format!("COLOR: {unknown}")));
+        }
+    }
+    for rgb in config.color_rgb.iter().flatten() {
+        if color_from_rgb_entry(rgb).is_none() {
+            issues.push(Issue::Error(format!(
+                "COLOR_RGB: '{rgb}' isn't an r,g,b triple or hex color"
+            )));
+        }
+    }
+    if let Some(ref gradient) = config.gradient {
+        if parse_gradient(gradient).is_none() {
+            issues.push(Issue::Error(format!(
+                "GRADIENT: '{gradient}' isn't two r,g,b or hex colors like 255,0,0:0,0,255"
+            )));
+        }
+    }
+    for attr in config.styles.iter().flatten() {
+        if !STYLE_ATTRS.contains(&attr.as_str()) {
+            issues.push(Issue::Error(format!("STYLE: unknown style '{attr}'")));
+        }
+    }
+
+    if let Some(ref path) = config.aliases {
+        if !Path::new(path).is_file() {
+            issues.push(Issue::Error(format!("ALIASES: {path} doesn't exist")));
+        }
+    }
+    if let Some(ref dir) = config.pack {
+        if !Path::new(dir).join("index.json").is_file() {
+            issues.push(Issue::Error(format!("PACK: {dir} has no index.json")));
+        }
+    }
+    if let Some(ref paths) = config.affirmations {
+        for path in split_affirmation_paths(paths) {
+            validate_affirmations(path, config, &mut issues);
+        }
+    }
+
+    issues
+}
+
+/// Print a report of every issue and return the exit code: 1 if anything
+/// is broken, 0 otherwise
+fn report(issues: &[Issue], out: &mut impl Write) -> io::Result<i32> {
+    let mut errors = 0;
+    for issue in issues {
+        match issue {
+            Issue::Error(message) => {
+                errors += 1;
+                writeln!(out, "error: {message}")?;
+            }
+            Issue::Warning(message) => writeln!(out, "warning: {message}")?,
+        }
+    }
+
+    if issues.is_empty() {
+        writeln!(out, "everything looks good~")?;
+    } else {
+        writeln!(
+            out,
+            "{errors} error(s), {} warning(s)",
+            issues.len() - errors
+        )?;
+    }
+    Ok(i32::from(errors > 0))
+}
+
+/// Check the whole configuration, see `report` for the exit code
+pub fn run_validate(config: &ConfigMommy, out: &mut impl Write) -> io::Result<i32> {
+    report(&validate(config), out)
+}
+
+/// Check a single affirmations file and list the moods in it, see `report`
+/// for the exit code
+pub fn run_validate_file(
+    path: &str,
+    config: &ConfigMommy,
+    out: &mut impl Write,
+) -> io::Result<i32> {
+    let mut issues = Vec::new();
+    for mood in validate_affirmations(path, config, &mut issues) {
+        writeln!(
+            out,
+            "mood {}: {} positive, {} negative",
+            mood.name, mood.positive, mood.negative
+        )?;
+    }
+    report(&issues, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{load_config, ENV_TEST_LOCK};
+    use std::env;
+
+    #[test]
+    fn test_validate_reports_all_issues() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let path = env::temp_dir().join(format!("mommy-test-validate-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"{"moods": {"chill": {"positive": ["good {litle}~"], "negative": []}}}"#,
+        )
+        .unwrap();
+
+        let mut config = load_config();
+        config.colors = vec!["blurple".to_string()];
+        config.color_rgb = None;
+        config.styles = vec![vec!["bold".to_string()]];
+        config.aliases = None;
+        config.pack = None;
+        config.affirmations = Some(path.display().to_string());
+
+        let mut out = Vec::new();
+        let code = run_validate(&config, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // Expect: both the bad color and the typo'd placeholder, and a failure
+        assert!(out.contains("error: COLOR: unknown color 'blurple'"));
+        assert!(out.contains("warning: AFFIRMATIONS: unknown placeholder {litle}"));
+        assert_eq!(code, 1);
+    }
+
+    #[test]
+    fn test_validate_file() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let config = load_config();
+        let path = env::temp_dir().join(format!(
+            "mommy-test-validate-file-{}.json",
+            std::process::id()
+        ));
+        fs::write(
+            &path,
+            r#"{"moods": {"chill": {"positive": ["good {litle}~"], "negative": []},
+                "thirsty": {"positive": ["{?neddy:a}"], "negative": ["b", "c"]}}}"#,
+        )
+        .unwrap();
+
+        let mut out = Vec::new();
+        let code = run_validate_file(&path.display().to_string(), &config, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        // Expect: the moods with their counts, the empty pool and the typos,
+        // but nothing that breaks the file
+        assert!(out.contains("mood chill: 1 positive, 0 negative"));
+        assert!(out.contains("mood thirsty: 1 positive, 2 negative"));
+        assert!(out.contains("warning: AFFIRMATIONS: mood 'chill' has no negative lines"));
+        assert!(out.contains("warning: AFFIRMATIONS: unknown placeholder {litle}"));
+        assert!(out.contains("warning: AFFIRMATIONS: unknown placeholder {?neddy}"));
+        assert_eq!(code, 0);
+
+        // Expect: a parse error says where it is, and fails
+        fs::write(&path, "{\"moods\": {\n  \"chill\": [}").unwrap();
+        let mut out = Vec::new();
+        let code = run_validate_file(&path.display().to_string(), &config, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(out.contains("line 2 column"), "{out}");
+        assert_eq!(code, 1);
+    }
+
+    #[test]
+    fn test_validate_clean_config() {
+        let _lock = ENV_TEST_LOCK.lock().unwrap();
+        let mut config = load_config();
+        config.colors = vec!["red".to_string()];
+        config.color_rgb = Some(vec!["255,0,0".to_string()]);
+        config.styles = vec![vec!["bold".to_string(), "italic".to_string()]];
+        config.aliases = None;
+        config.pack = None;
+        config.affirmations = None;
+
+        let mut out = Vec::new();
+        assert_eq!(run_validate(&config, &mut out).unwrap(), 0);
+        assert_eq!(String::from_utf8(out).unwrap(), "everything looks good~\n");
+    }
+}